@@ -0,0 +1,260 @@
+//! Sound effects and background music. The sounds are synthesized when the
+//! game starts, so there are no sound files to ship.
+//!
+//! Sound is built only with the `audio` feature (`cargo run --features
+//! audio`), which needs ALSA on Linux. Without it, without a sound device,
+//! or if a sound cannot be loaded, the game plays on in silence.
+
+use macroquad::audio::{PlaySoundParams, Sound, load_sound_from_bytes, play_sound, set_sound_volume, stop_sound};
+
+use crate::settings::Settings;
+
+const SAMPLE_RATE: u32 = 22050;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SoundEvent {
+    PaddleHit,
+    BrickHit,
+    BrickDestroyed,
+    WallBounce,
+    LifeLost,
+    LevelCleared,
+    GameOver,
+}
+
+impl SoundEvent {
+    const ALL: [SoundEvent; 7] = [
+        SoundEvent::PaddleHit,
+        SoundEvent::BrickHit,
+        SoundEvent::BrickDestroyed,
+        SoundEvent::WallBounce,
+        SoundEvent::LifeLost,
+        SoundEvent::LevelCleared,
+        SoundEvent::GameOver,
+    ];
+
+    fn samples(self) -> Vec<f32> {
+        match self {
+            SoundEvent::PaddleHit => tone(Wave::Square, 440.0, 520.0, 0.06),
+            SoundEvent::BrickHit => tone(Wave::Square, 660.0, 700.0, 0.05),
+            SoundEvent::BrickDestroyed => mix(&tone(Wave::Square, 880.0, 1320.0, 0.1), &tone(Wave::Noise, 0.0, 0.0, 0.08)),
+            SoundEvent::WallBounce => tone(Wave::Triangle, 300.0, 300.0, 0.04),
+            SoundEvent::LifeLost => tone(Wave::Triangle, 400.0, 100.0, 0.6),
+            SoundEvent::LevelCleared => notes(Wave::Square, &[523.25, 659.25, 783.99, 1046.5], 0.1),
+            SoundEvent::GameOver => notes(Wave::Triangle, &[392.0, 329.63, 261.63, 196.0], 0.25),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Wave {
+    Square,
+    Triangle,
+    Noise,
+}
+
+/// `seconds` of a wave gliding from `from` to `to` hertz, fading out.
+fn tone(wave: Wave, from: f32, to: f32, seconds: f32) -> Vec<f32> {
+    let count = (seconds * SAMPLE_RATE as f32) as usize;
+    let mut phase = 0.0f32;
+    // A fixed pseudo-random sequence, so the noise doesn't disturb the
+    // game's random numbers.
+    let mut noise = 0x2545_f491u32;
+    (0..count)
+        .map(|i| {
+            let progress = i as f32 / count as f32;
+            phase = (phase + (from + (to - from) * progress) / SAMPLE_RATE as f32).fract();
+            let value = match wave {
+                Wave::Square => {
+                    if phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                Wave::Noise => {
+                    noise ^= noise << 13;
+                    noise ^= noise >> 17;
+                    noise ^= noise << 5;
+                    noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+                }
+            };
+            value * 0.5 * (1.0 - progress)
+        })
+        .collect()
+}
+
+/// The notes one after another, each `seconds` long.
+fn notes(wave: Wave, frequencies: &[f32], seconds: f32) -> Vec<f32> {
+    frequencies
+        .iter()
+        .flat_map(|&frequency| tone(wave, frequency, frequency, seconds))
+        .collect()
+}
+
+/// Two sounds played together.
+fn mix(a: &[f32], b: &[f32]) -> Vec<f32> {
+    (0..a.len().max(b.len()))
+        .map(|i| (a.get(i).unwrap_or(&0.0) + b.get(i).unwrap_or(&0.0)).clamp(-1.0, 1.0))
+        .collect()
+}
+
+/// A quiet looping tune: an arpeggio over a walking bass line.
+fn music() -> Vec<f32> {
+    const CHORDS: [[f32; 3]; 4] = [
+        [261.63, 329.63, 392.0],
+        [220.0, 261.63, 329.63],
+        [174.61, 220.0, 261.63],
+        [196.0, 246.94, 293.66],
+    ];
+    let mut samples = Vec::new();
+    for chord in CHORDS {
+        for bar in 0..2 {
+            let arpeggio = notes(Wave::Triangle, &[chord[0], chord[1], chord[2], chord[1]].repeat(2), 0.125);
+            let bass = notes(Wave::Square, &[chord[0] / 4.0, chord[0] / 4.0 * if bar == 0 { 1.5 } else { 1.0 }], 0.5);
+            samples.extend(mix(&arpeggio, &bass.iter().map(|sample| sample * 0.4).collect::<Vec<_>>()));
+        }
+    }
+    samples.iter().map(|sample| sample * 0.6).collect()
+}
+
+/// 16-bit mono WAV file holding the samples.
+fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_size = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data_size).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes()); // PCM
+    bytes.extend(1u16.to_le_bytes()); // mono
+    bytes.extend(SAMPLE_RATE.to_le_bytes());
+    bytes.extend((SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data_size.to_le_bytes());
+    for sample in samples {
+        bytes.extend(((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    bytes
+}
+
+async fn load(name: &str, samples: &[f32]) -> Option<Sound> {
+    match load_sound_from_bytes(&wav(samples)).await {
+        Ok(sound) => Some(sound),
+        Err(e) => {
+            eprintln!("Could not load the {} sound, playing without it: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Whether there is a device to play sound on. Macroquad's sound thread
+/// gives up silently when there isn't, and every sound played after that
+/// prints an error, so the game checks first.
+fn has_sound_device() -> bool {
+    if cfg!(target_os = "linux") {
+        // ALSA lists the sound cards here, or says there are none.
+        std::fs::read_to_string("/proc/asound/cards").is_ok_and(|cards| !cards.contains("no soundcards"))
+    } else {
+        true
+    }
+}
+
+pub struct Audio {
+    effects: Vec<(SoundEvent, Sound)>,
+    music: Option<Sound>,
+    /// The music's volume as last set; zero while it is stopped.
+    music_volume: f32,
+}
+
+impl Audio {
+    pub async fn load() -> Audio {
+        let mut audio = Audio {
+            effects: Vec::new(),
+            music: None,
+            music_volume: 0.0,
+        };
+        if !cfg!(feature = "audio") {
+            return audio;
+        }
+        if !has_sound_device() {
+            eprintln!("No sound device found, playing without sound");
+            return audio;
+        }
+
+        for event in SoundEvent::ALL {
+            if let Some(sound) = load(&format!("{:?}", event), &event.samples()).await {
+                audio.effects.push((event, sound));
+            }
+        }
+        audio.music = load("music", &music()).await;
+        audio
+    }
+
+    pub fn play(&self, event: SoundEvent, settings: &Settings) {
+        let volume = settings.master_volume * settings.sfx_volume;
+        if volume <= 0.0 {
+            return;
+        }
+        if let Some((_, sound)) = self.effects.iter().find(|(e, _)| *e == event) {
+            play_sound(sound, PlaySoundParams { looped: false, volume });
+        }
+    }
+
+    /// Starts, stops or changes the volume of the music to match the
+    /// settings. Called every frame; it does nothing unless they changed.
+    pub fn update_music(&mut self, settings: &Settings) {
+        let Some(music) = &self.music else {
+            return;
+        };
+        let volume = settings.master_volume * settings.music_volume;
+        if volume == self.music_volume {
+            return;
+        }
+        if volume <= 0.0 {
+            stop_sound(music);
+        } else if self.music_volume <= 0.0 {
+            play_sound(music, PlaySoundParams { looped: true, volume });
+        } else {
+            set_sound_volume(music, volume);
+        }
+        self.music_volume = volume;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_header_describes_the_samples() {
+        let bytes = wav(&[0.0, 1.0, -1.0, 2.0]);
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 44);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), SAMPLE_RATE);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 8);
+        assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), i16::MAX);
+        // Out of range samples are clipped.
+        assert_eq!(i16::from_le_bytes([bytes[50], bytes[51]]), i16::MAX);
+    }
+
+    #[test]
+    fn every_sound_is_audible_and_in_range() {
+        for samples in SoundEvent::ALL.map(SoundEvent::samples).into_iter().chain([music()]) {
+            assert!(!samples.is_empty());
+            assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+            assert!(samples.iter().any(|sample| sample.abs() > 0.1));
+        }
+    }
+
+    #[test]
+    fn tones_last_as_long_as_asked() {
+        assert_eq!(tone(Wave::Square, 440.0, 440.0, 0.5).len(), SAMPLE_RATE as usize / 2);
+        assert_eq!(notes(Wave::Triangle, &[440.0, 880.0], 0.25).len(), 2 * tone(Wave::Triangle, 440.0, 440.0, 0.25).len());
+    }
+}
@@ -0,0 +1,341 @@
+//! The level editor, opened with E while the ball waits to be served or
+//! straight away with `--edit FILE`.
+//!
+//! Bricks snap to the level grid. Clicking an empty cell places a brick of
+//! the selected kind, and dragging keeps placing; clicking a brick cycles
+//! its kind. Right-click removes bricks. The mouse wheel or Tab picks the
+//! kind to place. Every change can be undone with Ctrl+Z and redone with
+//! Ctrl+Y; a drag counts as one change.
+//!
+//! Ctrl+S saves the level to its file in the format of `level`, Ctrl+O
+//! loads it back, Enter test-plays it (E returns to the editor) and Escape
+//! goes back to the game.
+
+use std::fs;
+
+use macroquad::prelude::*;
+
+use crate::level::{BrickKind, Level, MAX_COLUMNS, MAX_ROWS};
+use crate::{brick_position, Brick, BRICK_HEIGHT, BRICK_WIDTH, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+/// Changes that can be undone, oldest first.
+const MAX_UNDO: usize = 100;
+
+type Grid = Vec<Vec<Option<BrickKind>>>;
+
+/// What the game should do after the editor's frame.
+#[derive(Clone, PartialEq, Debug)]
+pub enum EditorAction {
+    None,
+    TestPlay(Level),
+    Quit,
+}
+
+pub struct Editor {
+    cells: Grid,
+    /// The kind of brick placed by clicking.
+    brush: BrickKind,
+    undo: Vec<Grid>,
+    redo: Vec<Grid>,
+    /// Whether the mouse button that started the current change is held.
+    dragging: bool,
+    path: String,
+    status: String,
+}
+
+impl Editor {
+    /// Opens the level at `path`, or an empty one if there is no file yet.
+    pub fn open(path: &str) -> Editor {
+        let mut editor = Editor {
+            cells: vec![vec![None; MAX_COLUMNS]; MAX_ROWS],
+            brush: BrickKind::Normal,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            dragging: false,
+            path: path.to_string(),
+            status: String::new(),
+        };
+        if fs::metadata(path).is_ok() {
+            editor.load();
+            editor.undo.clear();
+        } else {
+            editor.status = format!("New level, Ctrl+S saves it to {}", path);
+        }
+        editor
+    }
+
+    /// The level as edited: full-width rows, so it plays where it was
+    /// placed, without the empty rows at the bottom.
+    pub fn level(&self) -> Result<Level, String> {
+        let rows = self.cells.iter().rposition(|row| row.iter().any(Option::is_some)).map_or(0, |last| last + 1);
+        let level = Level {
+            name: String::new(),
+            rows: self.cells[..rows].to_vec(),
+        };
+        // Reading the text back checks the level as a file would be checked,
+        // and names it after the file.
+        Level::parse(&self.path, &level.to_text()).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&mut self) {
+        self.status = match self.level() {
+            Ok(level) => match fs::write(&self.path, level.to_text()) {
+                Ok(()) => format!("Saved {}", self.path),
+                Err(e) => format!("{}: {}", self.path, e),
+            },
+            Err(e) => e,
+        };
+    }
+
+    /// Replaces the grid with the saved level. This can be undone too.
+    pub fn load(&mut self) {
+        let level = fs::read_to_string(&self.path)
+            .map_err(|e| format!("{}: {}", self.path, e))
+            .and_then(|text| Level::parse(&self.path, &text).map_err(|e| e.to_string()));
+        match level {
+            Ok(level) => {
+                self.begin_change();
+                for (row, cells) in self.cells.iter_mut().enumerate() {
+                    for (col, cell) in cells.iter_mut().enumerate() {
+                        *cell = level.rows.get(row).and_then(|cells| cells.get(col)).copied().flatten();
+                    }
+                }
+                self.end_change();
+                self.status = format!("Loaded {}", self.path);
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    /// Remembers the grid before a change, for undo.
+    fn begin_change(&mut self) {
+        self.undo.push(self.cells.clone());
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Forgets the change just begun if it turned out not to change
+    /// anything; otherwise there is nothing left to redo.
+    fn end_change(&mut self) {
+        if self.undo.last() == Some(&self.cells) {
+            self.undo.pop();
+        } else {
+            self.redo.clear();
+        }
+    }
+
+    fn place(&mut self, row: usize, col: usize) {
+        self.cells[row][col].get_or_insert(self.brush);
+    }
+
+    fn erase(&mut self, row: usize, col: usize) {
+        self.cells[row][col] = None;
+    }
+
+    fn cycle(&mut self, row: usize, col: usize) {
+        if let Some(kind) = &mut self.cells[row][col] {
+            *kind = kind.next();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(cells) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.cells, cells));
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(cells) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.cells, cells));
+        }
+    }
+
+    /// The grid cell under the point, if any.
+    fn cell_at(point: Vec2) -> Option<(usize, usize)> {
+        (0..MAX_ROWS)
+            .flat_map(|row| (0..MAX_COLUMNS).map(move |col| (row, col)))
+            .find(|&(row, col)| Editor::cell_rect(row, col).contains(point))
+    }
+
+    /// The cell's brick with the gap around it, so there is no dead space
+    /// between cells.
+    fn cell_rect(row: usize, col: usize) -> Rect {
+        let position = brick_position(MAX_COLUMNS, row, col);
+        let gap = crate::BRICK_GAP / 2.0;
+        Rect::new(position.x - gap, position.y - gap, BRICK_WIDTH + 2.0 * gap, BRICK_HEIGHT + 2.0 * gap)
+    }
+
+    /// Handles this frame's mouse and keys.
+    pub fn update(&mut self) -> EditorAction {
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        if ctrl && is_key_pressed(KeyCode::Z) {
+            self.undo();
+        }
+        if ctrl && is_key_pressed(KeyCode::Y) {
+            self.redo();
+        }
+        if ctrl && is_key_pressed(KeyCode::S) {
+            self.save();
+        }
+        if ctrl && is_key_pressed(KeyCode::O) {
+            self.load();
+        }
+        if is_key_pressed(KeyCode::Tab) || mouse_wheel().1 < 0.0 {
+            self.brush = self.brush.next();
+        }
+        if mouse_wheel().1 > 0.0 {
+            for _ in 1..BrickKind::ALL.len() {
+                self.brush = self.brush.next();
+            }
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            self.begin_change();
+            self.cells = vec![vec![None; MAX_COLUMNS]; MAX_ROWS];
+            self.end_change();
+        }
+
+        let cell = Editor::cell_at(mouse_position().into());
+        let left = is_mouse_button_pressed(MouseButton::Left);
+        let right = is_mouse_button_pressed(MouseButton::Right);
+        if (left || right) && !self.dragging {
+            self.begin_change();
+            self.dragging = true;
+            if let Some((row, col)) = cell {
+                match (left, self.cells[row][col]) {
+                    (true, Some(_)) => self.cycle(row, col),
+                    (true, None) => self.place(row, col),
+                    (false, _) => self.erase(row, col),
+                }
+            }
+        } else if self.dragging
+            && let Some((row, col)) = cell
+        {
+            if is_mouse_button_down(MouseButton::Left) {
+                self.place(row, col);
+            } else if is_mouse_button_down(MouseButton::Right) {
+                self.erase(row, col);
+            }
+        }
+        if self.dragging && !is_mouse_button_down(MouseButton::Left) && !is_mouse_button_down(MouseButton::Right) {
+            self.dragging = false;
+            self.end_change();
+        }
+
+        if is_key_pressed(KeyCode::Enter) {
+            match self.level() {
+                Ok(level) => return EditorAction::TestPlay(level),
+                Err(e) => self.status = e,
+            }
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return EditorAction::Quit;
+        }
+        EditorAction::None
+    }
+
+    pub fn draw(&self) {
+        clear_background(BLACK);
+
+        for row in 0..MAX_ROWS {
+            for col in 0..MAX_COLUMNS {
+                let position = brick_position(MAX_COLUMNS, row, col);
+                match self.cells[row][col] {
+                    Some(kind) => Brick::new(position.x, position.y, kind).draw(),
+                    None => draw_rectangle_lines(position.x, position.y, BRICK_WIDTH, BRICK_HEIGHT, 1.0, DARKGRAY),
+                }
+            }
+        }
+        if let Some((row, col)) = Editor::cell_at(mouse_position().into()) {
+            let position = brick_position(MAX_COLUMNS, row, col);
+            draw_rectangle_lines(position.x, position.y, BRICK_WIDTH, BRICK_HEIGHT, 3.0, YELLOW);
+        }
+
+        draw_text(&format!("EDITING {}", self.path), 20.0, 30.0, 24.0, WHITE);
+        let brush = format!("PLACING: {}", self.brush.name());
+        let brush_size = measure_text(&brush, None, 24, 1.0);
+        draw_text(&brush, WINDOW_WIDTH - brush_size.width - 20.0, 30.0, 24.0, YELLOW);
+
+        let help = [
+            "CLICK: PLACE/CYCLE  RIGHT-CLICK: REMOVE  WHEEL/TAB: BRICK KIND",
+            "CTRL+Z/Y: UNDO/REDO  CTRL+S/O: SAVE/LOAD  DEL: CLEAR",
+            "ENTER: TEST PLAY  ESC: BACK TO GAME",
+        ];
+        for (line, text) in help.iter().enumerate() {
+            draw_text(text, 20.0, WINDOW_HEIGHT - 80.0 + line as f32 * 22.0, 20.0, LIGHTGRAY);
+        }
+        draw_text(&self.status, 20.0, WINDOW_HEIGHT - 100.0, 20.0, SKYBLUE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(path: &str) -> Editor {
+        Editor::open(&std::env::temp_dir().join(path).to_string_lossy())
+    }
+
+    /// One click: a change of its own, as `update` makes it.
+    fn click(editor: &mut Editor, change: impl FnOnce(&mut Editor)) {
+        editor.begin_change();
+        change(editor);
+        editor.end_change();
+    }
+
+    #[test]
+    fn placing_cycling_and_erasing_can_be_undone() {
+        let mut editor = editor("arkanoid-editor-undo.txt");
+        click(&mut editor, |editor| editor.place(0, 0));
+        click(&mut editor, |editor| editor.cycle(0, 0));
+        click(&mut editor, |editor| editor.erase(0, 0));
+        assert_eq!(editor.cells[0][0], None);
+
+        editor.undo();
+        assert_eq!(editor.cells[0][0], Some(BrickKind::Strong));
+        editor.undo();
+        assert_eq!(editor.cells[0][0], Some(BrickKind::Normal));
+        editor.redo();
+        assert_eq!(editor.cells[0][0], Some(BrickKind::Strong));
+
+        click(&mut editor, |editor| editor.place(1, 1));
+        assert!(editor.redo.is_empty());
+    }
+
+    #[test]
+    fn a_drag_is_one_change_and_no_op_clicks_are_none() {
+        let mut editor = editor("arkanoid-editor-drag.txt");
+        click(&mut editor, |editor| {
+            for col in 0..MAX_COLUMNS {
+                editor.place(2, col);
+            }
+        });
+        click(&mut editor, |editor| editor.erase(5, 5));
+        assert_eq!(editor.undo.len(), 1);
+
+        editor.undo();
+        assert!(editor.cells.iter().flatten().all(Option::is_none));
+    }
+
+    #[test]
+    fn saved_levels_load_back_and_play_in_place() {
+        let path = std::env::temp_dir().join(format!("arkanoid-editor-{}.txt", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let mut editor = Editor::open(&path);
+        assert!(editor.level().is_err());
+
+        editor.brush = BrickKind::Explosive;
+        click(&mut editor, |editor| editor.place(3, 8));
+        editor.save();
+        assert_eq!(editor.status, format!("Saved {}", path));
+
+        let level = editor.level().unwrap();
+        assert_eq!(level.rows.len(), 4);
+        assert_eq!(level.columns(), MAX_COLUMNS);
+        assert_eq!(level.rows[3][8], Some(BrickKind::Explosive));
+
+        let reopened = Editor::open(&path);
+        assert_eq!(reopened.cells, editor.cells);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+//! Level files. A level is a text file with one character per brick, one
+//! line per row of bricks:
+//!
+//! ```text
+//! # Lines starting with '#' are comments; blank lines are skipped.
+//! 11111111
+//! 1.2..2.1
+//! X*3333*X
+//! ```
+//!
+//! - `.` or a space: no brick
+//! - `1`: a normal brick, broken by one hit
+//! - `2`, `3`: bricks that take two or three hits
+//! - `X`: an indestructible brick
+//! - `*`: an explosive brick, which destroys its neighbours when it breaks
+//!
+//! The game plays the levels in `levels/` in order of file name. These are
+//! built into the game; `--levels DIR` plays the `.txt` files in another
+//! directory instead.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Widest row a level may have.
+pub const MAX_COLUMNS: usize = 9;
+/// Most rows a level may have, leaving room above the paddle.
+pub const MAX_ROWS: usize = 12;
+
+const BUILT_IN: [(&str, &str); 4] = [
+    ("levels/01_warm_up.txt", include_str!("../levels/01_warm_up.txt")),
+    ("levels/02_fortress.txt", include_str!("../levels/02_fortress.txt")),
+    ("levels/03_powder_keg.txt", include_str!("../levels/03_powder_keg.txt")),
+    ("levels/04_citadel.txt", include_str!("../levels/04_citadel.txt")),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrickKind {
+    Normal,
+    Strong,
+    Hard,
+    Indestructible,
+    Explosive,
+}
+
+impl BrickKind {
+    pub const ALL: [BrickKind; 5] = [
+        BrickKind::Normal,
+        BrickKind::Strong,
+        BrickKind::Hard,
+        BrickKind::Indestructible,
+        BrickKind::Explosive,
+    ];
+
+    fn from_char(c: char) -> Option<Option<BrickKind>> {
+        match c {
+            '.' | ' ' => Some(None),
+            '1' => Some(Some(BrickKind::Normal)),
+            '2' => Some(Some(BrickKind::Strong)),
+            '3' => Some(Some(BrickKind::Hard)),
+            'X' => Some(Some(BrickKind::Indestructible)),
+            '*' => Some(Some(BrickKind::Explosive)),
+            _ => None,
+        }
+    }
+
+    fn to_char(cell: Option<BrickKind>) -> char {
+        match cell {
+            None => '.',
+            Some(BrickKind::Normal) => '1',
+            Some(BrickKind::Strong) => '2',
+            Some(BrickKind::Hard) => '3',
+            Some(BrickKind::Indestructible) => 'X',
+            Some(BrickKind::Explosive) => '*',
+        }
+    }
+
+    /// The next kind in `ALL`, wrapping around.
+    pub fn next(self) -> BrickKind {
+        let index = BrickKind::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+        BrickKind::ALL[(index + 1) % BrickKind::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BrickKind::Normal => "NORMAL",
+            BrickKind::Strong => "STRONG",
+            BrickKind::Hard => "HARD",
+            BrickKind::Indestructible => "INDESTRUCTIBLE",
+            BrickKind::Explosive => "EXPLOSIVE",
+        }
+    }
+
+    /// Hits the brick takes to break.
+    pub fn health(self) -> i32 {
+        match self {
+            BrickKind::Strong => 2,
+            BrickKind::Hard => 3,
+            _ => 1,
+        }
+    }
+
+    /// Whether the level is cleared without breaking bricks of this kind.
+    pub fn is_breakable(self) -> bool {
+        self != BrickKind::Indestructible
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Level {
+    /// The file name without its extension.
+    pub name: String,
+    /// Rows from the top; `None` leaves a gap.
+    pub rows: Vec<Vec<Option<BrickKind>>>,
+}
+
+/// A problem with a level file, at a line if it is about one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LevelError {
+    pub path: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl Level {
+    /// Reads the level in `text`; `path` names it in errors and gives the
+    /// level its name.
+    pub fn parse(path: &str, text: &str) -> Result<Level, LevelError> {
+        let error = |line: Option<usize>, message: String| LevelError {
+            path: path.to_string(),
+            line,
+            message,
+        };
+
+        let mut rows = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut row = Vec::new();
+            for (column, c) in line.chars().enumerate() {
+                let cell = BrickKind::from_char(c).ok_or_else(|| {
+                    error(
+                        Some(line_number),
+                        format!("unknown brick '{}' in column {}, expected one of . 1 2 3 X *", c, column + 1),
+                    )
+                })?;
+                row.push(cell);
+            }
+            if row.len() > MAX_COLUMNS {
+                return Err(error(
+                    Some(line_number),
+                    format!("row is {} bricks wide, at most {} fit", row.len(), MAX_COLUMNS),
+                ));
+            }
+            if rows.len() == MAX_ROWS {
+                return Err(error(Some(line_number), format!("more than {} rows", MAX_ROWS)));
+            }
+            rows.push(row);
+        }
+
+        let breakable = rows.iter().flatten().flatten().any(|kind| kind.is_breakable());
+        if !breakable {
+            return Err(error(None, "level has no bricks to break".to_string()));
+        }
+
+        let name = Path::new(path)
+            .file_stem()
+            .map_or_else(|| path.to_string(), |stem| stem.to_string_lossy().into_owned());
+        Ok(Level { name, rows })
+    }
+
+    /// The name as shown in play: `02_fortress` becomes `FORTRESS`.
+    pub fn title(&self) -> String {
+        self.name
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '_')
+            .replace('_', " ")
+            .to_uppercase()
+    }
+
+    /// The level in the file format, as `parse` reads it.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("# {}\n", self.name);
+        for row in &self.rows {
+            text.extend(row.iter().map(|&cell| BrickKind::to_char(cell)));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Bricks in the widest row.
+    pub fn columns(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// The levels that ship with the game.
+    pub fn built_in() -> Vec<Level> {
+        BUILT_IN
+            .iter()
+            .map(|(path, text)| Level::parse(path, text).unwrap_or_else(|e| panic!("{}", e)))
+            .collect()
+    }
+
+    /// The `.txt` files in `dir`, in order of file name. Every file is
+    /// checked, so all the errors are reported at once.
+    pub fn load_dir(dir: &str) -> Result<Vec<Level>, Vec<LevelError>> {
+        let io_error = |path: &str, e: std::io::Error| LevelError {
+            path: path.to_string(),
+            line: None,
+            message: e.to_string(),
+        };
+
+        let entries = fs::read_dir(dir).map_err(|e| vec![io_error(dir, e)])?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+            .collect();
+        paths.sort();
+
+        let mut levels = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            let path = path.to_string_lossy().into_owned();
+            match fs::read_to_string(&path) {
+                Ok(text) => match Level::parse(&path, &text) {
+                    Ok(level) => levels.push(level),
+                    Err(e) => errors.push(e),
+                },
+                Err(e) => errors.push(io_error(&path, e)),
+            }
+        }
+
+        if levels.is_empty() && errors.is_empty() {
+            errors.push(LevelError {
+                path: dir.to_string(),
+                line: None,
+                message: "no .txt level files".to_string(),
+            });
+        }
+        if errors.is_empty() { Ok(levels) } else { Err(errors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bricks_gaps_and_comments() {
+        let level = Level::parse("levels/test.txt", "# a comment\n\n1.2\n  X*3  \n").unwrap();
+        assert_eq!(level.name, "test");
+        assert_eq!(
+            level.rows,
+            vec![
+                vec![Some(BrickKind::Normal), None, Some(BrickKind::Strong)],
+                vec![None, None, Some(BrickKind::Indestructible), Some(BrickKind::Explosive), Some(BrickKind::Hard)],
+            ]
+        );
+        assert_eq!(level.columns(), 5);
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let e = Level::parse("bad.txt", "# header\n111\n1q1\n").unwrap_err();
+        assert_eq!(e.line, Some(3));
+        assert!(e.to_string().starts_with("bad.txt:3: unknown brick 'q' in column 2"), "{}", e);
+
+        let e = Level::parse("wide.txt", "1111111111\n").unwrap_err();
+        assert_eq!(e.to_string(), "wide.txt:1: row is 10 bricks wide, at most 9 fit");
+
+        let e = Level::parse("tall.txt", &"1\n".repeat(MAX_ROWS + 1)).unwrap_err();
+        assert_eq!(e.line, Some(MAX_ROWS + 1));
+
+        let e = Level::parse("walls.txt", "XXX\n...\n").unwrap_err();
+        assert_eq!(e.to_string(), "walls.txt: level has no bricks to break");
+    }
+
+    #[test]
+    fn levels_round_trip_through_text() {
+        for level in Level::built_in() {
+            let text = level.to_text();
+            assert_eq!(Level::parse(&format!("{}.txt", level.name), &text), Ok(level));
+        }
+    }
+
+    #[test]
+    fn built_in_levels_are_valid() {
+        let levels = Level::built_in();
+        assert_eq!(levels.len(), BUILT_IN.len());
+        assert_eq!(levels[0].name, "01_warm_up");
+        assert_eq!(levels[0].title(), "WARM UP");
+    }
+
+    #[test]
+    fn loading_a_directory_reports_every_bad_file() {
+        let dir = std::env::temp_dir().join(format!("arkanoid-levels-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.txt"), "111\n").unwrap();
+        fs::write(dir.join("a.txt"), "222\n").unwrap();
+        fs::write(dir.join("notes.md"), "not a level").unwrap();
+        let dir_name = dir.to_string_lossy().into_owned();
+
+        let levels = Level::load_dir(&dir_name).unwrap();
+        assert_eq!(levels.iter().map(|level| level.name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+
+        fs::write(dir.join("c.txt"), "1\n?\n").unwrap();
+        fs::write(dir.join("d.txt"), "").unwrap();
+        let errors = Level::load_dir(&dir_name).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, Some(2));
+        assert!(errors[1].path.ends_with("d.txt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Level::load_dir(&dir_name).is_err());
+    }
+}
@@ -1,19 +1,57 @@
 use macroquad::prelude::*;
 
+mod audio;
+mod editor;
+mod level;
+mod particles;
+mod physics;
+mod powerup;
+mod scores;
+mod settings;
+mod state;
+
+use audio::{Audio, SoundEvent};
+use editor::{Editor, EditorAction};
+use level::{BrickKind, Level};
+use particles::{Burst, Particles, Shake};
+use physics::move_and_bounce;
+use powerup::{Capsule, Effects, PowerUp, DEFAULT_DROP_CHANCE};
+use scores::{Entry, HighScores, NameEntry};
+use settings::{Settings, SettingsMenu};
+use state::{Event, PauseChoice, PauseMenu, State};
+
 const WINDOW_WIDTH: f32 = 800.0;
 const WINDOW_HEIGHT: f32 = 600.0;
 const PADDLE_WIDTH: f32 = 150.0;
 const PADDLE_HEIGHT: f32 = 20.0;
-const PADDLE_SPEED: f32 = 10.0;
+/// Speeds are in pixels per second.
+const PADDLE_SPEED: f32 = 600.0;
 const BALL_SIZE: f32 = 15.0;
-const BALL_SPEED: f32 = 5.0;
+const BALL_SPEED: f32 = 300.0;
 const BRICK_WIDTH: f32 = 80.0;
 const BRICK_HEIGHT: f32 = 30.0;
-const BRICK_ROWS: usize = 5;
-const BRICK_COLS: usize = 8;
 const BRICK_GAP: f32 = 5.0;
+const BRICKS_TOP: f32 = 50.0;
 const PADDLE_Y_OFFSET: f32 = 40.0;
 const BOTTOM_BOUNDARY: f32 = WINDOW_HEIGHT - 20.0;
+const MAX_BALLS: usize = 12;
+const LASER_SPEED: f32 = 480.0;
+const LASER_WIDTH: f32 = 4.0;
+const LASER_HEIGHT: f32 = 12.0;
+/// Seconds between laser shots.
+const LASER_COOLDOWN: f32 = 0.3;
+/// The game is simulated in steps of this many seconds, however fast it is
+/// drawn.
+const TIMESTEP: f32 = 1.0 / 120.0;
+/// Longest frame simulated in full; after a longer stall the game slows down
+/// rather than taking many steps at once.
+const MAX_FRAME_TIME: f32 = 0.25;
+/// Most particles on screen at once.
+const MAX_PARTICLES: usize = 2048;
+/// Seconds a point of the ball's trail lasts.
+const TRAIL_LIFETIME: f32 = 0.12;
+/// Where the name is typed in for a high score, under "GAME OVER!".
+const NAME_ENTRY_Y: f32 = WINDOW_HEIGHT / 2.0 + 40.0;
 
 struct Paddle {
     rect: Rect,
@@ -31,25 +69,54 @@ impl Paddle {
         }
     }
 
-    fn update(&mut self) {
-        let x_move = match (is_key_down(KeyCode::Left), is_key_down(KeyCode::Right)) {
-            (true, false) => -PADDLE_SPEED,
-            (false, true) => PADDLE_SPEED,
-            _ => 0.0,
-        };
+    fn update(&mut self, seconds: f32, settings: &Settings) {
+        if let Some(target) = settings.pointer_x() {
+            let center = settings.follow(self.rect.center().x, target, seconds);
+            self.rect.x = center - self.rect.w / 2.0;
+        } else {
+            let x_move = match (is_key_down(KeyCode::Left), is_key_down(KeyCode::Right)) {
+                (true, false) => -PADDLE_SPEED,
+                (false, true) => PADDLE_SPEED,
+                _ => 0.0,
+            };
+            self.rect.x += x_move * seconds;
+        }
 
-        self.rect.x += x_move;
         self.rect.x = self.rect.x.clamp(0.0, WINDOW_WIDTH - self.rect.w);
     }
 
-    fn draw(&self) {
-        draw_rectangle(self.rect.x, self.rect.y, self.rect.w, self.rect.h, BLUE);
+    /// Resizes the paddle around its center.
+    fn set_width(&mut self, width: f32) {
+        let center = self.rect.center().x;
+        self.rect.w = width;
+        self.rect.x = (center - width / 2.0).clamp(0.0, WINDOW_WIDTH - width);
+    }
+
+    /// Where the laser bolts leave the paddle, one at each end.
+    fn cannons(&self) -> [Vec2; 2] {
+        [
+            vec2(self.rect.x + 6.0, self.rect.y),
+            vec2(self.rect.right() - 6.0, self.rect.y),
+        ]
+    }
+
+    fn draw(&self, effects: &Effects) {
+        let color = if effects.is_sticky() { DARKGREEN } else { BLUE };
+        draw_rectangle(self.rect.x, self.rect.y, self.rect.w, self.rect.h, color);
+        if effects.has_laser() {
+            for cannon in self.cannons() {
+                draw_rectangle(cannon.x - 4.0, cannon.y - 6.0, 8.0, 6.0, RED);
+            }
+        }
     }
 }
 
 struct Ball {
     rect: Rect,
     vel: Vec2,
+    /// Set while the ball is caught by a sticky paddle: how far from the
+    /// paddle's left edge it sits.
+    stuck: Option<f32>,
 }
 
 impl Ball {
@@ -62,30 +129,50 @@ impl Ball {
                 BALL_SIZE,
             ),
             vel: vec2(0.0, 0.0),
+            stuck: None,
         }
     }
 
-    fn update(&mut self, paddle: &Paddle, waiting_to_start: bool) {
+    /// Moves the ball for `seconds`, bouncing off the walls, the bricks
+    /// still standing and the paddle. Returns what it hit, as brick indices
+    /// with `None` for the paddle, and whether it bounced off a wall.
+    fn update(&mut self, paddle: &Paddle, bricks: &[Brick], waiting_to_start: bool, seconds: f32) -> (Vec<Option<usize>>, bool) {
         if waiting_to_start {
             self.rect.x = paddle.rect.x + (paddle.rect.w - self.rect.w) / 2.0;
-            return;
+            return (Vec::new(), false);
+        }
+        if let Some(offset) = self.stuck {
+            self.rect.x = paddle.rect.x + offset.clamp(0.0, paddle.rect.w - self.rect.w);
+            self.rect.y = paddle.rect.y - self.rect.h;
+            return (Vec::new(), false);
         }
 
-        self.rect.x += self.vel.x;
-        self.rect.y += self.vel.y;
+        // The paddle comes last, after the bricks.
+        let standing: Vec<usize> = (0..bricks.len()).filter(|&i| bricks[i].health > 0).collect();
+        let mut obstacles: Vec<Rect> = standing.iter().map(|&i| bricks[i].rect).collect();
+        obstacles.push(paddle.rect);
+        let hits = move_and_bounce(&mut self.rect, &mut self.vel, seconds, &obstacles)
+            .into_iter()
+            .map(|hit| standing.get(hit).copied())
+            .collect();
 
+        let mut wall = false;
         if self.rect.x < 0.0 {
             self.rect.x = 0.0;
             self.vel.x *= -1.0;
+            wall = true;
         }
         if self.rect.x > WINDOW_WIDTH - self.rect.w {
             self.rect.x = WINDOW_WIDTH - self.rect.w;
             self.vel.x *= -1.0;
+            wall = true;
         }
         if self.rect.y < 0.0 {
             self.rect.y = 0.0;
             self.vel.y *= -1.0;
+            wall = true;
         }
+        (hits, wall)
     }
 
     fn draw(&self) {
@@ -101,148 +188,630 @@ struct Brick {
     rect: Rect,
     color: Color,
     health: i32,
+    kind: BrickKind,
 }
 
 impl Brick {
-    fn new(x: f32, y: f32) -> Self {
+    fn new(x: f32, y: f32, kind: BrickKind) -> Self {
         Self {
             rect: Rect::new(x, y, BRICK_WIDTH, BRICK_HEIGHT),
-            color: Color::from_rgba(
-                rand::gen_range(100, 255),
-                rand::gen_range(100, 255),
-                rand::gen_range(100, 255),
-                255,
-            ),
-            health: 1,
+            color: brick_color(kind, kind.health()),
+            health: kind.health(),
+            kind,
         }
     }
 
+    /// Takes `damage` off the brick's health, or all of it if `damage` is
+    /// `None`. Returns whether the brick broke.
+    fn damage(&mut self, damage: Option<i32>) -> bool {
+        if self.health <= 0 || !self.kind.is_breakable() {
+            return false;
+        }
+        self.health = damage.map_or(0, |damage| (self.health - damage).max(0));
+        self.color = brick_color(self.kind, self.health);
+        self.health == 0
+    }
+
     fn draw(&self) {
         if self.health > 0 {
             draw_rectangle(self.rect.x, self.rect.y, self.rect.w, self.rect.h, self.color);
             draw_rectangle_lines(self.rect.x, self.rect.y, self.rect.w, self.rect.h, 2.0, DARKGRAY);
+            match self.kind {
+                BrickKind::Indestructible => {
+                    draw_rectangle_lines(self.rect.x + 6.0, self.rect.y + 6.0, self.rect.w - 12.0, self.rect.h - 12.0, 2.0, DARKGRAY);
+                }
+                BrickKind::Explosive => {
+                    let center = self.rect.center();
+                    draw_circle(center.x, center.y, self.rect.h / 4.0, YELLOW);
+                }
+                _ => {}
+            }
         }
     }
 }
 
-fn resolve_collision(a: &mut Rect, vel: &mut Vec2, b: &Rect) -> bool {
-    if let Some(intersection) = a.intersect(*b) {
-        if intersection.w > intersection.h {
-            a.y = if vel.y > 0.0 {
-                b.y - a.h
-            } else {
-                b.y + b.h
-            };
-            vel.y *= -1.0;
+/// Indestructible and explosive bricks have colors of their own; the others
+/// change color as their health drops, so a cracked brick looks weaker.
+fn brick_color(kind: BrickKind, health: i32) -> Color {
+    match kind {
+        BrickKind::Indestructible => Color::from_rgba(140, 140, 150, 255),
+        BrickKind::Explosive => Color::from_rgba(230, 90, 40, 255),
+        _ if health >= 3 => Color::from_rgba(155, 89, 182, 255),
+        _ if health == 2 => Color::from_rgba(52, 152, 219, 255),
+        _ => Color::from_rgba(46, 204, 113, 255),
+    }
+}
+
+/// Hits brick `index` and returns the points scored and the bricks that
+/// broke, with the color each had before it broke. An explosive brick that
+/// breaks destroys the bricks around it, which may set off more explosives.
+fn hit_brick(bricks: &mut [Brick], index: usize) -> (i32, Vec<(Rect, Color)>) {
+    let mut score = 0;
+    let mut broken = Vec::new();
+    let mut pending = vec![(index, Some(1))];
+    while let Some((i, damage)) = pending.pop() {
+        if !bricks[i].kind.is_breakable() || bricks[i].health <= 0 {
+            continue;
+        }
+        score += 10;
+        let color = bricks[i].color;
+        if !bricks[i].damage(damage) {
+            continue;
+        }
+        broken.push((bricks[i].rect, color));
+        if bricks[i].kind == BrickKind::Explosive {
+            let rect = bricks[i].rect;
+            let blast = Rect::new(rect.x - rect.w / 2.0, rect.y - rect.h / 2.0, rect.w * 2.0, rect.h * 2.0);
+            for (j, other) in bricks.iter().enumerate() {
+                if other.health > 0 && other.rect.overlaps(&blast) {
+                    pending.push((j, None));
+                }
+            }
+        }
+    }
+    (score, broken)
+}
+
+/// Drops a capsule from each broken brick with probability `drop_chance`.
+fn drop_capsules(capsules: &mut Vec<Capsule>, broken: &[(Rect, Color)], drop_chance: f32) {
+    for (rect, _) in broken {
+        if rand::gen_range(0.0, 1.0) < drop_chance {
+            capsules.push(Capsule::new(rect.center(), PowerUp::random()));
+        }
+    }
+}
+
+/// Breaks the bricks into falling debris of their own color.
+fn spawn_debris(particles: &mut Particles, broken: &[(Rect, Color)]) {
+    for &(rect, color) in broken {
+        particles.burst(
+            rect,
+            &Burst {
+                count: 14,
+                color,
+                speed: 220.0,
+                direction: 0.0,
+                spread: std::f32::consts::PI,
+                size: 4.0,
+                lifetime: 0.9,
+                gravity: 900.0,
+            },
+        );
+    }
+}
+
+fn brick_sound(broken: &[(Rect, Color)]) -> SoundEvent {
+    if broken.is_empty() {
+        SoundEvent::BrickHit
+    } else {
+        SoundEvent::BrickDestroyed
+    }
+}
+
+/// Sparks flying up from where the ball met the paddle.
+fn spawn_sparks(particles: &mut Particles, ball: &Ball) {
+    let rect = ball.rect;
+    particles.burst(
+        Rect::new(rect.x, rect.bottom() - 2.0, rect.w, 2.0),
+        &Burst {
+            count: 10,
+            color: YELLOW,
+            speed: 260.0,
+            direction: 0.0,
+            spread: 1.2,
+            size: 2.0,
+            lifetime: 0.3,
+            gravity: 0.0,
+        },
+    );
+}
+
+/// Splits every ball into three, fanned out from its direction, up to
+/// `MAX_BALLS`. The new balls are never caught, even if the old one is.
+fn split_balls(balls: &mut Vec<Ball>) {
+    let mut new_balls = Vec::new();
+    for ball in balls.iter() {
+        let vel = if ball.stuck.is_some() || ball.vel == Vec2::ZERO {
+            vec2(0.0, -BALL_SPEED)
         } else {
-            a.x = if vel.x > 0.0 {
-                b.x - a.w
-            } else {
-                b.x + b.w
-            };
-            vel.x *= -1.0;
+            ball.vel
+        };
+        for angle in [-0.4f32, 0.4] {
+            new_balls.push(Ball {
+                rect: ball.rect,
+                vel: Vec2::from_angle(angle).rotate(vel),
+                stuck: None,
+            });
         }
-        return true;
     }
-    false
+    let room = MAX_BALLS.saturating_sub(balls.len());
+    balls.extend(new_balls.into_iter().take(room));
+}
+
+/// Draws `text` centered across the window with its baseline at `y`.
+fn draw_centered(text: &str, y: f32, font_size: f32, color: Color) {
+    let size = measure_text(text, None, font_size as u16, 1.0);
+    draw_text(text, WINDOW_WIDTH / 2.0 - size.width / 2.0, y, font_size, color);
+}
+
+fn draw_hint() {
+    draw_centered("PRESS S FOR SETTINGS, E FOR THE EDITOR", WINDOW_HEIGHT - 60.0, 24.0, GRAY);
 }
 
-fn create_bricks() -> Vec<Brick> {
-    let total_width = BRICK_COLS as f32 * BRICK_WIDTH + (BRICK_COLS - 1) as f32 * BRICK_GAP;
+/// The title screen, with the high scores and the latest one highlighted.
+fn draw_title(settings: &Settings, high_scores: &HighScores, new_rank: Option<usize>) {
+    clear_background(BLACK);
+    draw_centered("ARKANOID", 90.0, 90.0, RED);
+    draw_centered(&format!("{} TO START", settings.control.action_name()), 150.0, 40.0, YELLOW);
+    draw_centered("ESC OR P PAUSES THE GAME", 185.0, 24.0, LIGHTGRAY);
+    high_scores.draw(240.0, WINDOW_WIDTH, new_rank);
+    draw_hint();
+}
+
+/// Top-left corner of the brick at `row`, `col` of a level `columns` bricks
+/// wide, centered across the window.
+fn brick_position(columns: usize, row: usize, col: usize) -> Vec2 {
+    let total_width = columns as f32 * BRICK_WIDTH + columns.saturating_sub(1) as f32 * BRICK_GAP;
     let start_x = (WINDOW_WIDTH - total_width) / 2.0;
-    let start_y = 50.0;
-    
+    vec2(
+        start_x + col as f32 * (BRICK_WIDTH + BRICK_GAP),
+        BRICKS_TOP + row as f32 * (BRICK_HEIGHT + BRICK_GAP),
+    )
+}
+
+fn create_bricks(level: &Level) -> Vec<Brick> {
+    let columns = level.columns();
     let mut bricks = Vec::new();
-    for row in 0..BRICK_ROWS {
-        for col in 0..BRICK_COLS {
-            bricks.push(Brick::new(
-                start_x + col as f32 * (BRICK_WIDTH + BRICK_GAP),
-                start_y + row as f32 * (BRICK_HEIGHT + BRICK_GAP),
-            ));
+    for (row, cells) in level.rows.iter().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            if let Some(kind) = *cell {
+                let position = brick_position(columns, row, col);
+                bricks.push(Brick::new(position.x, position.y, kind));
+            }
         }
     }
     bricks
 }
 
+fn level_cleared(bricks: &[Brick]) -> bool {
+    bricks.iter().all(|brick| brick.health <= 0 || !brick.kind.is_breakable())
+}
+
+/// The value after `name` on the command line.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)).cloned()
+}
+
+/// The levels to play: the `.txt` files in the directory given with
+/// `--levels`, or the built-in ones.
+fn levels_from_args() -> Vec<Level> {
+    let Some(dir) = arg_value("--levels") else {
+        return Level::built_in();
+    };
+    match Level::load_dir(&dir) {
+        Ok(levels) => levels,
+        Err(errors) => {
+            for e in errors {
+                eprintln!("{}", e);
+            }
+            eprintln!("Playing the built-in levels instead");
+            Level::built_in()
+        }
+    }
+}
+
+fn drop_chance_from_args() -> f32 {
+    let Some(value) = arg_value("--drop-chance") else {
+        return DEFAULT_DROP_CHANCE;
+    };
+    match value.parse::<f32>() {
+        Ok(chance) if (0.0..=1.0).contains(&chance) => chance,
+        _ => {
+            eprintln!("--drop-chance must be between 0 and 1, got {}", value);
+            DEFAULT_DROP_CHANCE
+        }
+    }
+}
+
 #[macroquad::main("Arkanoid")]
 async fn main() {
     rand::srand(miniquad::date::now() as u64);
     
     let mut paddle = Paddle::new();
-    let mut ball = Ball::new(&paddle);
-    let mut bricks = create_bricks();
-    let mut game_over = false;
+    let mut balls = vec![Ball::new(&paddle)];
+    let mut capsules: Vec<Capsule> = Vec::new();
+    let mut bolts: Vec<Rect> = Vec::new();
+    let mut effects = Effects::default();
+    let mut laser_cooldown = 0.0;
+    let drop_chance = drop_chance_from_args();
+    let all_levels = levels_from_args();
+    let mut levels = all_levels.clone();
+    let mut level_index = 0;
+    let mut bricks = create_bricks(&levels[level_index]);
     let mut score = 0;
     let mut lives = 3;
-    let mut waiting_to_start = true;
-    let mut game_won = false;
+    let mut state = State::Title;
+    let mut pause_menu = PauseMenu::default();
+
+    let scores_path = HighScores::path();
+    let mut high_scores = match &scores_path {
+        Some(path) => HighScores::load(path),
+        None => {
+            eprintln!("No data directory found, high scores will not be saved");
+            HighScores::default()
+        }
+    };
+    let mut name_entry: Option<NameEntry> = None;
+    // Where the last game's score went in the table.
+    let mut new_rank = None;
+
+    let settings_path = Settings::path();
+    let mut settings = Settings::load(&settings_path);
+    let mut audio = Audio::load().await;
+    let mut settings_menu = SettingsMenu::default();
+    let mut accumulator = 0.0;
+    let mut particles = Particles::new(MAX_PARTICLES);
+    let mut shake = Shake::default();
+
+    let edit_path = arg_value("--edit");
+    let mut editing = edit_path.is_some();
+    let mut editor = Editor::open(edit_path.as_deref().unwrap_or("custom_level.txt"));
+    // Whether the game is playing the level from the editor.
+    let mut test_playing = false;
+    let mut restart = false;
 
     loop {
-        paddle.update();
-        ball.update(&paddle, waiting_to_start);
+        audio.update_music(&settings);
+
+        if restart {
+            restart = false;
+            paddle = Paddle::new();
+            balls = vec![Ball::new(&paddle)];
+            capsules.clear();
+            bolts.clear();
+            effects.clear();
+            level_index = 0;
+            bricks = create_bricks(&levels[level_index]);
+            score = 0;
+            lives = 3;
+            state = State::Serving;
+            new_rank = None;
+        }
+
+        if editing {
+            match editor.update() {
+                EditorAction::None => {}
+                EditorAction::TestPlay(level) => {
+                    levels = vec![level];
+                    test_playing = true;
+                    editing = false;
+                    restart = true;
+                }
+                EditorAction::Quit => {
+                    levels = all_levels.clone();
+                    test_playing = false;
+                    editing = false;
+                    state = State::Title;
+                }
+            }
+            editor.draw();
+            next_frame().await;
+            continue;
+        }
 
-        if !game_over && !waiting_to_start {
-            if resolve_collision(&mut ball.rect, &mut ball.vel, &paddle.rect) {
-                let hit_position = (ball.rect.center().x - paddle.rect.center().x) / (paddle.rect.w / 2.0);
-                ball.vel.x = hit_position * BALL_SPEED;
+        // The game stands still while the settings are open.
+        let in_settings = settings_menu.open;
+        let was_paused = matches!(state, State::Paused { .. });
+        let naming = name_entry.is_some();
+        if in_settings {
+            settings_menu.update(&mut settings, WINDOW_WIDTH);
+            if !settings_menu.open
+                && let Err(e) = settings.save(&settings_path)
+            {
+                eprintln!("Settings save failed: {}", e);
             }
+        } else if let Some(entry) = &mut name_entry {
+            if let Some(name) = entry.update(WINDOW_WIDTH, NAME_ENTRY_Y) {
+                name_entry = None;
+                new_rank = high_scores.insert(Entry {
+                    name,
+                    score,
+                    level: level_index + 1,
+                    date: scores::today(),
+                });
+                if let Some(path) = &scores_path
+                    && let Err(e) = high_scores.save(path)
+                {
+                    eprintln!("High-score save failed: {}", e);
+                }
+            }
+        } else if was_paused {
+            if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::P) {
+                state = state.next(Event::Pause);
+            } else if let Some(choice) = pause_menu.update(WINDOW_WIDTH) {
+                match choice {
+                    PauseChoice::Resume => {}
+                    PauseChoice::Restart => restart = true,
+                    PauseChoice::Quit => {
+                        levels = all_levels.clone();
+                        test_playing = false;
+                    }
+                }
+                state = state.next(choice.event());
+            }
+        } else if matches!(state, State::Serving | State::Playing)
+            && (is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::P))
+        {
+            state = state.next(Event::Pause);
+        } else if state.is_between_games() && is_key_pressed(KeyCode::S) {
+            settings_menu.toggle();
+        } else if (state.is_between_games() || test_playing) && is_key_pressed(KeyCode::E) {
+            editing = true;
+        }
+        // The game also stands still while paused, including the frame it
+        // resumes on, so the key that resumed it does nothing else.
+        // Likewise while a name is typed in.
+        let frozen = in_settings || was_paused || naming || matches!(state, State::Paused { .. });
+
+        if !settings.visual_effects {
+            particles.clear();
+            shake.stop();
+        }
 
-            for brick in bricks.iter_mut() {
-                if brick.health > 0 && resolve_collision(&mut ball.rect, &mut ball.vel, &brick.rect) {
-                    brick.health -= 1;
-                    score += 10; 
-                    break;
+        let frame_time = get_frame_time().min(MAX_FRAME_TIME);
+        if !frozen {
+            accumulator += frame_time;
+        }
+        while accumulator >= TIMESTEP {
+            accumulator -= TIMESTEP;
+            let playing = state.is_playing();
+
+            paddle.set_width(PADDLE_WIDTH * effects.paddle_scale());
+            paddle.update(TIMESTEP, &settings);
+            particles.update(TIMESTEP);
+            shake.update(TIMESTEP);
+
+            if playing {
+                effects.tick(TIMESTEP);
+                laser_cooldown = (laser_cooldown - TIMESTEP).max(0.0);
+                if !effects.is_sticky() {
+                    for ball in &mut balls {
+                        ball.stuck = None;
+                    }
                 }
             }
 
-            if ball.rect.y > BOTTOM_BOUNDARY {
+            // Each ball sees the bricks as the balls before it left them.
+            for ball in &mut balls {
+                let (mut hits, wall) = ball.update(&paddle, &bricks, !playing, TIMESTEP * effects.ball_speed_scale());
+                if wall {
+                    audio.play(SoundEvent::WallBounce, &settings);
+                }
+                // The paddle can also run into the ball from the side.
+                if playing && ball.stuck.is_none() && ball.vel.y > 0.0 && physics::overlapping(&ball.rect, &paddle.rect) {
+                    ball.rect.y = paddle.rect.y - ball.rect.h;
+                    ball.vel.y = -ball.vel.y;
+                    hits.push(None);
+                }
+
+                if playing && ball.stuck.is_none() && settings.visual_effects {
+                    particles.emit(ball.rect.center(), Vec2::ZERO, Color::new(1.0, 0.3, 0.3, 0.5), BALL_SIZE / 2.0, TRAIL_LIFETIME, 0.0);
+                }
+
+                for hit in hits {
+                    if let Some(index) = hit {
+                        let (points, broken) = hit_brick(&mut bricks, index);
+                        score += points;
+                        drop_capsules(&mut capsules, &broken, drop_chance);
+                        if settings.visual_effects {
+                            spawn_debris(&mut particles, &broken);
+                        }
+                        audio.play(brick_sound(&broken), &settings);
+                    } else if ball.vel.y < 0.0 {
+                        audio.play(SoundEvent::PaddleHit, &settings);
+                        if settings.visual_effects {
+                            spawn_sparks(&mut particles, ball);
+                        }
+                        let hit_position = (ball.rect.center().x - paddle.rect.center().x) / (paddle.rect.w / 2.0);
+                        ball.vel.x = hit_position * BALL_SPEED;
+                        if effects.is_sticky() {
+                            ball.stuck = Some(ball.rect.x - paddle.rect.x);
+                        }
+                    }
+                }
+            }
+
+            if !playing {
+                continue;
+            }
+
+            bolts.retain_mut(|bolt| {
+                bolt.y -= LASER_SPEED * TIMESTEP;
+                let hit = bricks.iter().position(|brick| brick.health > 0 && brick.rect.overlaps(bolt));
+                if let Some(index) = hit {
+                    let (points, broken) = hit_brick(&mut bricks, index);
+                    score += points;
+                    drop_capsules(&mut capsules, &broken, drop_chance);
+                    if settings.visual_effects {
+                        spawn_debris(&mut particles, &broken);
+                    }
+                    audio.play(brick_sound(&broken), &settings);
+                }
+                hit.is_none() && bolt.bottom() > 0.0
+            });
+
+            let mut caught = Vec::new();
+            capsules.retain_mut(|capsule| {
+                capsule.update(TIMESTEP);
+                if capsule.rect.overlaps(&paddle.rect) {
+                    caught.push(capsule.power_up);
+                    return false;
+                }
+                capsule.rect.y < BOTTOM_BOUNDARY
+            });
+            for power_up in caught {
+                match power_up {
+                    PowerUp::MultiBall => split_balls(&mut balls),
+                    PowerUp::ExtraLife => lives += 1,
+                    _ => {
+                        effects.start(power_up);
+                    }
+                }
+            }
+
+            if level_cleared(&bricks) {
+                audio.play(SoundEvent::LevelCleared, &settings);
+                state = state.next(Event::LevelCleared {
+                    last: level_index + 1 >= levels.len(),
+                });
+            }
+
+            balls.retain(|ball| ball.rect.y <= BOTTOM_BOUNDARY);
+            if state.is_playing() && balls.is_empty() {
+                if settings.visual_effects {
+                    shake.start(10.0, 0.4);
+                }
                 lives -= 1;
-                if lives <= 0 {
-                    game_over = true;
-                    game_won = false;
+                state = state.next(Event::BallLost { lives });
+                if state == State::GameOver {
+                    audio.play(SoundEvent::GameOver, &settings);
                 } else {
-                    waiting_to_start = true;
-                    ball = Ball::new(&paddle);
+                    audio.play(SoundEvent::LifeLost, &settings);
                 }
             }
 
-            if bricks.iter().all(|b| b.health <= 0) {
-                game_over = true;
-                game_won = true;
+            // Games of a level from the editor don't count.
+            if matches!(state, State::GameOver | State::Victory) && !test_playing && high_scores.qualifies(score) {
+                name_entry = Some(NameEntry::new());
+            }
+
+            // Power-ups last only until the ball is next served.
+            if !state.is_playing() {
+                balls = vec![Ball::new(&paddle)];
+                capsules.clear();
+                bolts.clear();
+                effects.clear();
+            }
+        }
+
+        let before = state;
+        if !frozen {
+            state = state.next(Event::Tick(frame_time));
+        }
+        if !frozen && !settings_menu.open && name_entry.is_none() && settings.action_pressed() {
+            if state == State::Title {
+                restart = true;
+            } else if state.is_playing() {
+                if balls.iter().any(|ball| ball.stuck.is_some()) {
+                    for ball in &mut balls {
+                        ball.stuck = None;
+                    }
+                } else if effects.has_laser() && laser_cooldown == 0.0 {
+                    for cannon in paddle.cannons() {
+                        bolts.push(Rect::new(cannon.x - LASER_WIDTH / 2.0, cannon.y - LASER_HEIGHT, LASER_WIDTH, LASER_HEIGHT));
+                    }
+                    laser_cooldown = LASER_COOLDOWN;
+                }
+            } else {
+                state = state.next(Event::Action);
+                // A finished test play goes back to the full set of levels.
+                if state == State::Title {
+                    levels = all_levels.clone();
+                    test_playing = false;
+                }
+                if state.is_playing() {
+                    for ball in &mut balls {
+                        ball.launch();
+                    }
+                }
             }
         }
+        // The next level is set up once the cleared one has been shown.
+        if matches!(before, State::LevelCleared { .. }) && !matches!(state, State::LevelCleared { .. }) {
+            level_index += 1;
+            bricks = create_bricks(&levels[level_index]);
+        }
 
-        if is_key_pressed(KeyCode::Space) {
-            if game_over {
-                // Reset game
-                paddle = Paddle::new();
-                ball = Ball::new(&paddle);
-                bricks = create_bricks();
-                game_over = false;
-                score = 0;
-                lives = 3;
-                waiting_to_start = true;
-            } else if waiting_to_start {
-                waiting_to_start = false;
-                ball.launch();
+        if state == State::Title {
+            draw_title(&settings, &high_scores, new_rank);
+            if settings_menu.open {
+                settings_menu.draw(&settings, WINDOW_WIDTH, WINDOW_HEIGHT);
             }
+            next_frame().await;
+            continue;
         }
 
         clear_background(BLACK);
 
+        shake.apply();
         draw_line(0.0, BOTTOM_BOUNDARY, WINDOW_WIDTH, BOTTOM_BOUNDARY, 2.0, GRAY);
 
-        paddle.draw();
-        ball.draw();
         for brick in &bricks {
             brick.draw();
         }
+        particles.draw();
+        paddle.draw(&effects);
+        for ball in &balls {
+            ball.draw();
+        }
+        for capsule in &capsules {
+            capsule.draw();
+        }
+        for bolt in &bolts {
+            draw_rectangle(bolt.x, bolt.y, bolt.w, bolt.h, YELLOW);
+        }
+        set_default_camera();
 
         draw_text(&format!("SCORE: {}", score), 20.0, 30.0, 30.0, WHITE);
         draw_text(&format!("LIVES: {}", lives), WINDOW_WIDTH - 120.0, 30.0, 30.0, WHITE);
+        let level_text = if test_playing {
+            "TEST PLAY: E TO EDIT".to_string()
+        } else {
+            format!("LEVEL {}/{}", level_index + 1, levels.len())
+        };
+        let level_size = measure_text(&level_text, None, 30, 1.0);
+        draw_text(&level_text, WINDOW_WIDTH / 2.0 - level_size.width / 2.0, 30.0, 30.0, WHITE);
+
+        let mut effect_x = 20.0;
+        for (power_up, seconds) in effects.active() {
+            let text = format!("{} {:.0}", power_up.name(), seconds.ceil());
+            draw_text(&text, effect_x, WINDOW_HEIGHT - 4.0, 20.0, power_up.color());
+            effect_x += measure_text(&text, None, 20, 1.0).width + 20.0;
+        }
 
-        if waiting_to_start && !game_over {
-            let text = "PRESS SPACE TO LAUNCH";
+        if state == State::Serving {
+            let title = format!("LEVEL {}: {}", level_index + 1, levels[level_index].title());
+            let title_size = measure_text(&title, None, 40, 1.0);
+            draw_text(
+                &title,
+                WINDOW_WIDTH / 2.0 - title_size.width / 2.0,
+                WINDOW_HEIGHT / 2.0,
+                40.0,
+                WHITE,
+            );
+
+            let text = &format!("{} TO LAUNCH", settings.control.action_name());
             let text_size = measure_text(text, None, 40, 1.0);
             draw_text(
                 text,
@@ -252,7 +821,7 @@ async fn main() {
                 YELLOW,
             );
             
-            let instructions = "CONTROLS: LEFT/RIGHT ARROWS";
+            let instructions = settings.control.instructions();
             let inst_size = measure_text(instructions, None, 30, 1.0);
             draw_text(
                 instructions,
@@ -263,7 +832,15 @@ async fn main() {
             );
         }
 
-        if game_over {
+        match state {
+            State::LifeLost { .. } => draw_centered("BALL LOST!", WINDOW_HEIGHT / 2.0, 60.0, ORANGE),
+            State::LevelCleared { .. } => draw_centered("LEVEL CLEARED!", WINDOW_HEIGHT / 2.0, 60.0, GREEN),
+            State::Paused { .. } => pause_menu.draw(WINDOW_WIDTH, WINDOW_HEIGHT),
+            _ => {}
+        }
+
+        if matches!(state, State::GameOver | State::Victory) {
+            let game_won = state == State::Victory;
             let text = if game_won {
                 "YOU WIN!"
             } else {
@@ -279,17 +856,55 @@ async fn main() {
                 if game_won { GREEN } else { RED },
             );
             
-            let restart_text = "PRESS SPACE TO PLAY AGAIN";
-            let restart_size = measure_text(restart_text, None, 40, 1.0);
-            draw_text(
-                restart_text,
-                WINDOW_WIDTH / 2.0 - restart_size.width / 2.0,
-                WINDOW_HEIGHT / 2.0 + 40.0,
-                40.0,
-                YELLOW,
-            );
+            if let Some(entry) = &name_entry {
+                entry.draw(WINDOW_WIDTH, NAME_ENTRY_Y);
+            } else {
+                let restart_text = &format!("{} TO CONTINUE", settings.control.action_name());
+                let restart_size = measure_text(restart_text, None, 40, 1.0);
+                draw_text(
+                    restart_text,
+                    WINDOW_WIDTH / 2.0 - restart_size.width / 2.0,
+                    WINDOW_HEIGHT / 2.0 + 40.0,
+                    40.0,
+                    YELLOW,
+                );
+            }
+        }
+
+        if state.is_between_games() && name_entry.is_none() {
+            draw_hint();
+        }
+        if settings_menu.open {
+            settings_menu.draw(&settings, WINDOW_WIDTH, WINDOW_HEIGHT);
         }
 
         next_frame().await;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_brick_broken_by_one_ball_lets_the_next_through() {
+        let mut bricks = vec![Brick::new(100.0, BRICKS_TOP, BrickKind::Normal)];
+        let paddle = Paddle::new();
+        let mut balls: Vec<Ball> = (0..2)
+            .map(|_| Ball {
+                rect: Rect::new(130.0, BRICKS_TOP + BRICK_HEIGHT + 1.0, BALL_SIZE, BALL_SIZE),
+                vel: vec2(0.0, -BALL_SPEED),
+                stuck: None,
+            })
+            .collect();
+
+        let (hits, _) = balls[0].update(&paddle, &bricks, false, 0.05);
+        assert_eq!(hits, [Some(0)]);
+        assert!(balls[0].vel.y > 0.0);
+        assert_eq!(hit_brick(&mut bricks, 0).1.len(), 1);
+
+        let (hits, _) = balls[1].update(&paddle, &bricks, false, 0.05);
+        assert!(hits.is_empty());
+        assert!(balls[1].vel.y < 0.0);
+    }
+}
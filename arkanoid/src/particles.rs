@@ -0,0 +1,240 @@
+//! Particles and screen shake, the game's visual effects. Both can be turned
+//! off in the settings.
+//!
+//! Particles live in a pool allocated once. Each new particle takes the
+//! next slot in turn, so when the pool is full the oldest particle makes
+//! way and nothing is allocated while the game runs.
+
+use macroquad::prelude::*;
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    color: Color,
+    /// Radius when the particle is new; it shrinks as it fades.
+    size: f32,
+    /// Seconds left to live, and the seconds it started with.
+    life: f32,
+    lifetime: f32,
+    /// Downward acceleration, in pixels per second squared.
+    gravity: f32,
+}
+
+impl Particle {
+    const DEAD: Particle = Particle {
+        pos: Vec2::ZERO,
+        vel: Vec2::ZERO,
+        color: BLANK,
+        size: 0.0,
+        life: 0.0,
+        lifetime: 1.0,
+        gravity: 0.0,
+    };
+
+    fn is_alive(&self) -> bool {
+        self.life > 0.0
+    }
+}
+
+/// How a burst of particles looks and moves.
+#[derive(Clone, Copy, Debug)]
+pub struct Burst {
+    pub count: usize,
+    pub color: Color,
+    /// Pixels per second, each particle going at a random speed up to this.
+    pub speed: f32,
+    /// Directions the particles fly in, as an angle in radians around
+    /// straight up and how far either side of it they may stray.
+    pub direction: f32,
+    pub spread: f32,
+    pub size: f32,
+    pub lifetime: f32,
+    pub gravity: f32,
+}
+
+pub struct Particles {
+    pool: Vec<Particle>,
+    /// The slot the next particle goes in.
+    next: usize,
+}
+
+impl Particles {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            pool: vec![Particle::DEAD; capacity],
+            next: 0,
+        }
+    }
+
+    /// Adds one particle that lives `lifetime` seconds.
+    pub fn emit(&mut self, pos: Vec2, vel: Vec2, color: Color, size: f32, lifetime: f32, gravity: f32) {
+        if self.pool.is_empty() {
+            return;
+        }
+        self.pool[self.next] = Particle {
+            pos,
+            vel,
+            color,
+            size,
+            life: lifetime,
+            lifetime,
+            gravity,
+        };
+        self.next = (self.next + 1) % self.pool.len();
+    }
+
+    /// Throws particles out from random points inside `area`.
+    pub fn burst(&mut self, area: Rect, burst: &Burst) {
+        for _ in 0..burst.count {
+            let pos = vec2(
+                rand::gen_range(area.x, area.right()),
+                rand::gen_range(area.y, area.bottom()),
+            );
+            let angle = burst.direction + rand::gen_range(-burst.spread, burst.spread);
+            let vel = Vec2::from_angle(angle - std::f32::consts::FRAC_PI_2) * rand::gen_range(0.3, 1.0) * burst.speed;
+            let lifetime = burst.lifetime * rand::gen_range(0.6, 1.0);
+            self.emit(pos, vel, burst.color, burst.size, lifetime, burst.gravity);
+        }
+    }
+
+    pub fn update(&mut self, seconds: f32) {
+        for particle in self.pool.iter_mut().filter(|particle| particle.is_alive()) {
+            particle.vel.y += particle.gravity * seconds;
+            particle.pos += particle.vel * seconds;
+            particle.life -= seconds;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pool.fill(Particle::DEAD);
+    }
+
+    pub fn draw(&self) {
+        for particle in self.pool.iter().filter(|particle| particle.is_alive()) {
+            let fade = particle.life / particle.lifetime;
+            let mut color = particle.color;
+            color.a *= fade;
+            draw_circle(particle.pos.x, particle.pos.y, particle.size * fade.max(0.3), color);
+        }
+    }
+}
+
+/// Shakes the picture for a moment, strongest at the start.
+#[derive(Default)]
+pub struct Shake {
+    strength: f32,
+    time: f32,
+    duration: f32,
+}
+
+impl Shake {
+    /// Shakes by up to `strength` pixels for `duration` seconds, unless a
+    /// stronger shake is already going.
+    pub fn start(&mut self, strength: f32, duration: f32) {
+        if strength >= self.current_strength() {
+            self.strength = strength;
+            self.time = duration;
+            self.duration = duration;
+        }
+    }
+
+    pub fn update(&mut self, seconds: f32) {
+        self.time = (self.time - seconds).max(0.0);
+    }
+
+    pub fn stop(&mut self) {
+        self.time = 0.0;
+    }
+
+    fn current_strength(&self) -> f32 {
+        if self.time > 0.0 {
+            self.strength * self.time / self.duration
+        } else {
+            0.0
+        }
+    }
+
+    /// How far to move the picture this frame.
+    pub fn offset(&self) -> Vec2 {
+        let strength = self.current_strength();
+        vec2(rand::gen_range(-1.0, 1.0), rand::gen_range(-1.0, 1.0)) * strength
+    }
+
+    /// Draws what follows moved by the shake. `set_default_camera` undoes it.
+    pub fn apply(&self) {
+        let offset = self.offset();
+        set_camera(&Camera2D {
+            target: vec2(screen_width() / 2.0, screen_height() / 2.0) + offset,
+            zoom: vec2(2.0 / screen_width(), 2.0 / screen_height()),
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(particles: &Particles) -> usize {
+        particles.pool.iter().filter(|particle| particle.is_alive()).count()
+    }
+
+    #[test]
+    fn the_pool_never_grows() {
+        let mut particles = Particles::new(16);
+        for _ in 0..40 {
+            particles.emit(Vec2::ZERO, vec2(10.0, 0.0), WHITE, 2.0, 1.0, 0.0);
+        }
+        assert_eq!(live(&particles), 16);
+        assert_eq!(particles.pool.len(), 16);
+        assert_eq!(particles.pool.capacity(), 16);
+    }
+
+    #[test]
+    fn particles_move_fall_and_die() {
+        let mut particles = Particles::new(4);
+        particles.emit(Vec2::ZERO, vec2(100.0, 0.0), WHITE, 2.0, 0.5, 200.0);
+        particles.update(0.25);
+        let particle = particles.pool[0];
+        assert_eq!(particle.pos, vec2(25.0, 12.5));
+
+        particles.update(0.25);
+        assert_eq!(live(&particles), 0);
+    }
+
+    #[test]
+    fn bursts_start_inside_their_area() {
+        let mut particles = Particles::new(64);
+        let area = Rect::new(100.0, 50.0, 80.0, 30.0);
+        let burst = Burst {
+            count: 20,
+            color: RED,
+            speed: 100.0,
+            direction: 0.0,
+            spread: 0.5,
+            size: 3.0,
+            lifetime: 1.0,
+            gravity: 0.0,
+        };
+        particles.burst(area, &burst);
+        assert_eq!(live(&particles), 20);
+        for particle in particles.pool.iter().filter(|particle| particle.is_alive()) {
+            assert!(area.contains(particle.pos));
+            assert!(particle.vel.y < 0.0, "{:?} is not flying upwards", particle.vel);
+        }
+    }
+
+    #[test]
+    fn shakes_die_down() {
+        let mut shake = Shake::default();
+        assert_eq!(shake.offset(), Vec2::ZERO);
+        shake.start(8.0, 0.5);
+        shake.start(2.0, 1.0);
+        assert_eq!(shake.current_strength(), 8.0);
+        shake.update(0.25);
+        assert_eq!(shake.current_strength(), 4.0);
+        shake.update(1.0);
+        assert_eq!(shake.offset(), Vec2::ZERO);
+    }
+}
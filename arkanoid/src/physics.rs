@@ -0,0 +1,197 @@
+//! Swept collision for the ball. Rather than moving the ball and then
+//! looking for overlaps, which lets a fast ball skip over a brick between
+//! two steps, the ball's whole path for a step is tested against every
+//! obstacle and it bounces off the first one it would touch.
+
+use macroquad::prelude::*;
+
+/// Most bounces worked out in one step; anything left of the step after
+/// that is dropped.
+const MAX_BOUNCES: usize = 4;
+/// Hits this close together in time count as simultaneous, as when the ball
+/// meets the seam between two bricks.
+const SIMULTANEOUS: f32 = 1e-4;
+
+/// Where along a motion a moving rectangle first touches another.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hit {
+    /// Fraction of the motion made before touching, from 0 to 1.
+    pub time: f32,
+    /// Unit normal of the side that was hit, pointing back at the mover.
+    pub normal: Vec2,
+}
+
+/// Whether `a` and `b` overlap by more than touching.
+pub fn overlapping(a: &Rect, b: &Rect) -> bool {
+    a.x < b.right() && b.x < a.right() && a.y < b.bottom() && b.y < a.bottom()
+}
+
+/// Casts `moving` along `motion` against `target`. Rectangles that already
+/// overlap are not a hit, so something stuck inside an obstacle can leave it.
+pub fn sweep(moving: Rect, motion: Vec2, target: Rect) -> Option<Hit> {
+    if overlapping(&moving, &target) {
+        return None;
+    }
+
+    // Sweeping a rectangle against a rectangle is casting its corner
+    // against the target grown by the mover's size.
+    let min = vec2(target.x - moving.w, target.y - moving.h);
+    let max = vec2(target.right(), target.bottom());
+    let origin = moving.point();
+
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        if motion[axis] == 0.0 {
+            if origin[axis] <= min[axis] || origin[axis] >= max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - origin[axis]) / motion[axis];
+        let t2 = (max[axis] - origin[axis]) / motion[axis];
+        let (near, far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+        if near > entry {
+            entry = near;
+            normal = Vec2::ZERO;
+            normal[axis] = -motion[axis].signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+    Some(Hit { time: entry, normal })
+}
+
+/// Moves `rect` by `vel` for `seconds`, bouncing off `obstacles`. Returns the
+/// indices of the obstacles hit, in the order they were hit; obstacles hit at
+/// the same moment all count, with the ball bouncing once per axis.
+pub fn move_and_bounce(rect: &mut Rect, vel: &mut Vec2, seconds: f32, obstacles: &[Rect]) -> Vec<usize> {
+    let mut hits = Vec::new();
+    let mut remaining = seconds;
+    for _ in 0..MAX_BOUNCES {
+        let motion = *vel * remaining;
+        let swept: Vec<(usize, Hit)> = obstacles
+            .iter()
+            .enumerate()
+            .filter_map(|(i, obstacle)| sweep(*rect, motion, *obstacle).map(|hit| (i, hit)))
+            .collect();
+        let Some(time) = swept.iter().map(|(_, hit)| hit.time).min_by(f32::total_cmp) else {
+            rect.move_to(rect.point() + motion);
+            break;
+        };
+
+        rect.move_to(rect.point() + motion * time);
+        let mut flip = (false, false);
+        for (i, hit) in swept.iter().filter(|(_, hit)| hit.time <= time + SIMULTANEOUS) {
+            hits.push(*i);
+            flip.0 |= hit.normal.x != 0.0;
+            flip.1 |= hit.normal.y != 0.0;
+        }
+        if flip.0 {
+            vel.x = -vel.x;
+        }
+        if flip.1 {
+            vel.y = -vel.y;
+        }
+        remaining *= 1.0 - time;
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f32 = 1.0 / 120.0;
+
+    #[test]
+    fn sweep_finds_the_first_touch() {
+        let ball = Rect::new(0.0, 100.0, 10.0, 10.0);
+        let brick = Rect::new(-20.0, 0.0, 80.0, 30.0);
+
+        let hit = sweep(ball, vec2(0.0, -140.0), brick).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, vec2(0.0, 1.0));
+
+        assert_eq!(sweep(ball, vec2(0.0, -50.0), brick), None);
+        assert_eq!(sweep(ball, vec2(0.0, 140.0), brick), None);
+        assert_eq!(sweep(ball, vec2(200.0, -140.0), brick), None);
+    }
+
+    #[test]
+    fn side_hits_bounce_sideways() {
+        let mut ball = Rect::new(0.0, 10.0, 10.0, 10.0);
+        let mut vel = vec2(600.0, 0.0);
+        let brick = Rect::new(15.0, 0.0, 80.0, 30.0);
+        assert_eq!(move_and_bounce(&mut ball, &mut vel, 0.1, &[brick]), vec![0]);
+        assert_eq!(vel, vec2(-600.0, 0.0));
+        assert!(ball.right() <= brick.x);
+    }
+
+    #[test]
+    fn fast_balls_never_tunnel_through_bricks() {
+        let bricks: Vec<Rect> = (0..9).map(|col| Rect::new(col as f32 * 85.0, 100.0, 80.0, 30.0)).collect();
+        for speed in [300.0, 5_000.0, 50_000.0, 500_000.0] {
+            for degrees in (-40..=40).step_by(5) {
+                let direction = Vec2::from_angle((degrees as f32 - 90.0).to_radians());
+                let mut ball = Rect::new(380.0, 500.0, 15.0, 15.0);
+                let mut vel = direction * speed;
+
+                let mut hits = Vec::new();
+                for _ in 0..240 {
+                    hits.extend(move_and_bounce(&mut ball, &mut vel, STEP, &bricks));
+                    assert!(ball.y >= 130.0, "ball at {:?} went through at {} px/s, {} degrees", ball, speed, degrees);
+                    if !hits.is_empty() {
+                        break;
+                    }
+                }
+                assert!(!hits.is_empty(), "ball missed the bricks at {} px/s, {} degrees", speed, degrees);
+                assert!(vel.y > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn fast_balls_never_tunnel_through_the_paddle() {
+        let paddle = Rect::new(325.0, 560.0, 150.0, 20.0);
+        let mut ball = Rect::new(390.0, 100.0, 15.0, 15.0);
+        let mut vel = vec2(0.0, 200_000.0);
+        assert_eq!(move_and_bounce(&mut ball, &mut vel, STEP, &[paddle]), vec![0]);
+        assert!(ball.bottom() <= paddle.y);
+        assert!(vel.y < 0.0);
+    }
+
+    #[test]
+    fn hitting_a_seam_hits_both_bricks_and_bounces_once() {
+        let left = Rect::new(0.0, 0.0, 80.0, 30.0);
+        let right = Rect::new(80.0, 0.0, 80.0, 30.0);
+        let mut ball = Rect::new(75.0, 100.0, 10.0, 10.0);
+        let mut vel = vec2(0.0, -12_000.0);
+
+        let hits = move_and_bounce(&mut ball, &mut vel, STEP, &[left, right]);
+        assert_eq!(hits, vec![0, 1]);
+        assert_eq!(vel, vec2(0.0, 12_000.0));
+        assert!(ball.y >= 30.0);
+    }
+
+    #[test]
+    fn several_bounces_fit_in_one_step() {
+        // A narrow shaft: the ball bounces off both walls and the ceiling.
+        let walls = [
+            Rect::new(-100.0, -100.0, 100.0, 400.0),
+            Rect::new(20.0, -100.0, 100.0, 400.0),
+            Rect::new(0.0, -100.0, 20.0, 100.0),
+        ];
+        let mut ball = Rect::new(5.0, 50.0, 10.0, 10.0);
+        let mut vel = vec2(600.0, -6_000.0);
+        let hits = move_and_bounce(&mut ball, &mut vel, STEP, &walls);
+        assert!(hits.len() >= 2, "{:?}", hits);
+        for wall in &walls {
+            assert!(!overlapping(&ball, wall), "{:?} inside {:?}", ball, wall);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Power-ups. A broken brick sometimes drops a capsule, and catching it with
+//! the paddle gives its power-up. Multi-ball and extra life act at once; the
+//! others last for a while and run out on their own. Every effect ends when
+//! a life is lost or a level is cleared.
+//!
+//! `--drop-chance P` sets the chance, from 0 to 1, that a broken brick drops
+//! a capsule.
+
+use macroquad::prelude::*;
+
+pub const DEFAULT_DROP_CHANCE: f32 = 0.15;
+/// Seconds a timed power-up lasts.
+pub const EFFECT_DURATION: f32 = 12.0;
+const CAPSULE_WIDTH: f32 = 40.0;
+const CAPSULE_HEIGHT: f32 = 16.0;
+/// Pixels a capsule falls per second.
+const CAPSULE_SPEED: f32 = 150.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerUp {
+    Enlarge,
+    Shrink,
+    MultiBall,
+    Slow,
+    Sticky,
+    Laser,
+    ExtraLife,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 7] = [
+        PowerUp::Enlarge,
+        PowerUp::Shrink,
+        PowerUp::MultiBall,
+        PowerUp::Slow,
+        PowerUp::Sticky,
+        PowerUp::Laser,
+        PowerUp::ExtraLife,
+    ];
+
+    pub fn random() -> PowerUp {
+        PowerUp::ALL[rand::gen_range(0, PowerUp::ALL.len())]
+    }
+
+    /// The letter on the capsule.
+    pub fn letter(self) -> &'static str {
+        match self {
+            PowerUp::Enlarge => "E",
+            PowerUp::Shrink => "R",
+            PowerUp::MultiBall => "M",
+            PowerUp::Slow => "S",
+            PowerUp::Sticky => "C",
+            PowerUp::Laser => "L",
+            PowerUp::ExtraLife => "P",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::Enlarge => "ENLARGE",
+            PowerUp::Shrink => "SHRINK",
+            PowerUp::MultiBall => "MULTI-BALL",
+            PowerUp::Slow => "SLOW",
+            PowerUp::Sticky => "CATCH",
+            PowerUp::Laser => "LASER",
+            PowerUp::ExtraLife => "EXTRA LIFE",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PowerUp::Enlarge => BLUE,
+            PowerUp::Shrink => GRAY,
+            PowerUp::MultiBall => SKYBLUE,
+            PowerUp::Slow => ORANGE,
+            PowerUp::Sticky => GREEN,
+            PowerUp::Laser => RED,
+            PowerUp::ExtraLife => PINK,
+        }
+    }
+}
+
+pub struct Capsule {
+    pub rect: Rect,
+    pub power_up: PowerUp,
+}
+
+impl Capsule {
+    pub fn new(center: Vec2, power_up: PowerUp) -> Self {
+        Self {
+            rect: Rect::new(
+                center.x - CAPSULE_WIDTH / 2.0,
+                center.y - CAPSULE_HEIGHT / 2.0,
+                CAPSULE_WIDTH,
+                CAPSULE_HEIGHT,
+            ),
+            power_up,
+        }
+    }
+
+    pub fn update(&mut self, seconds: f32) {
+        self.rect.y += CAPSULE_SPEED * seconds;
+    }
+
+    pub fn draw(&self) {
+        let radius = self.rect.h / 2.0;
+        let color = self.power_up.color();
+        draw_rectangle(self.rect.x + radius, self.rect.y, self.rect.w - 2.0 * radius, self.rect.h, color);
+        draw_circle(self.rect.x + radius, self.rect.y + radius, radius, color);
+        draw_circle(self.rect.right() - radius, self.rect.y + radius, radius, color);
+
+        let letter = self.power_up.letter();
+        let size = measure_text(letter, None, 18, 1.0);
+        let center = self.rect.center();
+        draw_text(letter, center.x - size.width / 2.0, center.y + size.height / 2.0, 18.0, BLACK);
+    }
+}
+
+/// Seconds left on each timed power-up; zero when it is off.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Effects {
+    pub enlarge: f32,
+    pub shrink: f32,
+    pub slow: f32,
+    pub sticky: f32,
+    pub laser: f32,
+}
+
+impl Effects {
+    /// Starts a timed power-up, or restarts its timer if it is already on.
+    /// Enlarge and shrink cancel each other. Returns false for power-ups
+    /// that are not timed.
+    pub fn start(&mut self, power_up: PowerUp) -> bool {
+        let timer = match power_up {
+            PowerUp::Enlarge => {
+                self.shrink = 0.0;
+                &mut self.enlarge
+            }
+            PowerUp::Shrink => {
+                self.enlarge = 0.0;
+                &mut self.shrink
+            }
+            PowerUp::Slow => &mut self.slow,
+            PowerUp::Sticky => &mut self.sticky,
+            PowerUp::Laser => &mut self.laser,
+            PowerUp::MultiBall | PowerUp::ExtraLife => return false,
+        };
+        *timer = EFFECT_DURATION;
+        true
+    }
+
+    pub fn tick(&mut self, seconds: f32) {
+        for timer in [&mut self.enlarge, &mut self.shrink, &mut self.slow, &mut self.sticky, &mut self.laser] {
+            *timer = (*timer - seconds).max(0.0);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Effects::default();
+    }
+
+    /// The paddle's width as a multiple of its normal width.
+    pub fn paddle_scale(&self) -> f32 {
+        if self.enlarge > 0.0 {
+            1.5
+        } else if self.shrink > 0.0 {
+            0.6
+        } else {
+            1.0
+        }
+    }
+
+    /// The balls' speed as a multiple of their normal speed.
+    pub fn ball_speed_scale(&self) -> f32 {
+        if self.slow > 0.0 { 0.6 } else { 1.0 }
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.sticky > 0.0
+    }
+
+    pub fn has_laser(&self) -> bool {
+        self.laser > 0.0
+    }
+
+    /// The timed power-ups that are on, with their seconds left.
+    pub fn active(&self) -> Vec<(PowerUp, f32)> {
+        [
+            (PowerUp::Enlarge, self.enlarge),
+            (PowerUp::Shrink, self.shrink),
+            (PowerUp::Slow, self.slow),
+            (PowerUp::Sticky, self.sticky),
+            (PowerUp::Laser, self.laser),
+        ]
+        .into_iter()
+        .filter(|&(_, seconds)| seconds > 0.0)
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_power_ups_run_out() {
+        let mut effects = Effects::default();
+        assert!(effects.start(PowerUp::Slow));
+        assert!(effects.start(PowerUp::Laser));
+        assert_eq!(effects.ball_speed_scale(), 0.6);
+
+        effects.tick(EFFECT_DURATION - 1.0);
+        assert!(effects.start(PowerUp::Laser));
+        effects.tick(2.0);
+        assert_eq!(effects.ball_speed_scale(), 1.0);
+        assert_eq!(effects.active(), vec![(PowerUp::Laser, EFFECT_DURATION - 2.0)]);
+    }
+
+    #[test]
+    fn enlarge_and_shrink_cancel_each_other() {
+        let mut effects = Effects::default();
+        effects.start(PowerUp::Enlarge);
+        assert_eq!(effects.paddle_scale(), 1.5);
+        effects.start(PowerUp::Shrink);
+        assert_eq!(effects.paddle_scale(), 0.6);
+        assert_eq!(effects.enlarge, 0.0);
+    }
+
+    #[test]
+    fn instant_power_ups_have_no_timer() {
+        let mut effects = Effects::default();
+        assert!(!effects.start(PowerUp::MultiBall));
+        assert!(!effects.start(PowerUp::ExtraLife));
+        assert_eq!(effects, Effects::default());
+
+        effects.start(PowerUp::Sticky);
+        effects.clear();
+        assert!(!effects.is_sticky());
+    }
+}
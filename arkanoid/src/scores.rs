@@ -0,0 +1,316 @@
+//! The high-score table: the ten best games with the player's name, the
+//! level reached and the date. It lives in the user's data directory
+//! (`$XDG_DATA_HOME/arkanoid` on Linux) so it is the same wherever the game
+//! is started from.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+const FILE_NAME: &str = "highscores.txt";
+/// The name given when none is typed.
+const DEFAULT_NAME: &str = "PLAYER";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub score: i32,
+    /// The level the game ended on, counting from 1.
+    pub level: usize,
+    /// The day the game was played, as YYYY-MM-DD.
+    pub date: String,
+}
+
+/// The best games, best first.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct HighScores {
+    pub entries: Vec<Entry>,
+}
+
+impl HighScores {
+    /// Where the table is kept, if the system has a data directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("arkanoid").join(FILE_NAME))
+    }
+
+    /// Whether `score` would make it into the table.
+    pub fn qualifies(&self, score: i32) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    /// Adds the entry in its place and returns its rank, counting from 0,
+    /// or `None` if it did not make the table. A tie ranks below the older
+    /// entry.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let rank = self.entries.iter().position(|other| entry.score > other.score).unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    /// One entry per line: score, level, date and name, separated by tabs.
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| format!("{}\t{}\t{}\t{}\n", entry.score, entry.level, entry.date, entry.name))
+            .collect()
+    }
+
+    pub fn parse(text: &str) -> Result<HighScores, String> {
+        let mut scores = HighScores::default();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            let fields: Vec<&str> = line.splitn(4, '\t').collect();
+            let [score, level, date, name] = fields[..] else {
+                return Err(error("expected score, level, date and name separated by tabs"));
+            };
+            scores.entries.push(Entry {
+                name: name.to_string(),
+                score: score.parse().map_err(|_| error("score must be a number"))?,
+                level: level.parse().map_err(|_| error("level must be a number"))?,
+                date: date.to_string(),
+            });
+        }
+        // Hand-edited files may be out of order or too long.
+        scores.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        scores.entries.truncate(MAX_ENTRIES);
+        Ok(scores)
+    }
+
+    /// Reads the table saved at `path`. A missing or unreadable file is not
+    /// fatal: a warning is printed and the table starts empty.
+    pub fn load(path: &Path) -> HighScores {
+        match fs::read_to_string(path) {
+            Ok(text) => HighScores::parse(&text).unwrap_or_else(|e| {
+                eprintln!("High-score file {} is invalid, {}; starting a new table", path.display(), e);
+                HighScores::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HighScores::default(),
+            Err(e) => {
+                eprintln!("High-score load failed: {}, starting a new table", e);
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+
+    /// Draws the table with its top at `y`, highlighting the entry at rank
+    /// `highlight`.
+    pub fn draw(&self, y: f32, screen_width: f32, highlight: Option<usize>) {
+        let columns = [(-300.0, "#"), (-250.0, "NAME"), (-10.0, "SCORE"), (120.0, "LEVEL"), (200.0, "DATE")];
+        let x = |offset: f32| screen_width / 2.0 + offset;
+        for (offset, heading) in columns {
+            draw_text(heading, x(offset), y, 22.0, GRAY);
+        }
+        if self.entries.is_empty() {
+            let text = "NO HIGH SCORES YET";
+            let size = measure_text(text, None, 26, 1.0);
+            draw_text(text, screen_width / 2.0 - size.width / 2.0, y + 36.0, 26.0, LIGHTGRAY);
+        }
+        for (rank, entry) in self.entries.iter().enumerate() {
+            let line_y = y + 30.0 + rank as f32 * 26.0;
+            let color = if Some(rank) == highlight { YELLOW } else { WHITE };
+            let cells = [
+                (rank + 1).to_string(),
+                entry.name.clone(),
+                entry.score.to_string(),
+                entry.level.to_string(),
+                entry.date.clone(),
+            ];
+            for ((offset, _), cell) in columns.iter().zip(&cells) {
+                draw_text(cell, x(*offset), line_y, 24.0, color);
+            }
+        }
+    }
+}
+
+/// Today's date as YYYY-MM-DD, in UTC.
+pub fn today() -> String {
+    date(miniquad::date::now() as i64)
+}
+
+/// The date `seconds` after the Unix epoch, as YYYY-MM-DD.
+fn date(seconds: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, counting in 400-year eras
+    // that start on March 1st so leap days fall at the end of the year.
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Typing in a name for the table.
+#[derive(Default)]
+pub struct NameEntry {
+    name: String,
+}
+
+impl NameEntry {
+    /// Starts with nothing typed, forgetting keys pressed during the game.
+    pub fn new() -> NameEntry {
+        clear_input_queue();
+        NameEntry::default()
+    }
+
+    fn type_char(&mut self, character: char) {
+        if (character.is_ascii_alphanumeric() || character == ' ' || character == '-')
+            && self.name.chars().count() < MAX_NAME_LENGTH
+            && !(character == ' ' && self.name.is_empty())
+        {
+            self.name.push(character.to_ascii_uppercase());
+        }
+    }
+
+    /// The name as it goes in the table.
+    fn finish(&self) -> String {
+        let name = self.name.trim();
+        if name.is_empty() { DEFAULT_NAME } else { name }.to_string()
+    }
+
+    /// The button that enters the name, under it when drawn at `y`.
+    fn ok_button(screen_width: f32, y: f32) -> Rect {
+        Rect::new(screen_width / 2.0 - 50.0, y + 75.0, 100.0, 40.0)
+    }
+
+    /// Handles this frame's typing. Returns the name once Enter is pressed
+    /// or the OK button is clicked; other clicks, such as one meant for the
+    /// game that just ended, do nothing.
+    pub fn update(&mut self, screen_width: f32, y: f32) -> Option<String> {
+        let mut typed = Vec::new();
+        while let Some(character) = get_char_pressed() {
+            typed.push(character);
+        }
+        // The queue hands back the last key first.
+        for character in typed.into_iter().rev() {
+            self.type_char(character);
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.name.pop();
+        }
+        let ok_clicked = is_mouse_button_pressed(MouseButton::Left)
+            && NameEntry::ok_button(screen_width, y).contains(mouse_position().into());
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) || ok_clicked {
+            return Some(self.finish());
+        }
+        None
+    }
+
+    pub fn draw(&self, screen_width: f32, y: f32) {
+        let title = "NEW HIGH SCORE! ENTER YOUR NAME:";
+        let title_size = measure_text(title, None, 30, 1.0);
+        draw_text(title, screen_width / 2.0 - title_size.width / 2.0, y, 30.0, WHITE);
+
+        // A blinking cursor after the name.
+        let cursor = if get_time().fract() < 0.5 { "_" } else { " " };
+        let text = format!("{}{}", self.name, cursor);
+        let size = measure_text(&format!("{}_", self.name), None, 40, 1.0);
+        draw_text(&text, screen_width / 2.0 - size.width / 2.0, y + 50.0, 40.0, YELLOW);
+
+        let button = NameEntry::ok_button(screen_width, y);
+        let hovered = button.contains(mouse_position().into());
+        draw_rectangle(button.x, button.y, button.w, button.h, if hovered { DARKGREEN } else { DARKGRAY });
+        draw_rectangle_lines(button.x, button.y, button.w, button.h, 2.0, WHITE);
+        let ok_size = measure_text("OK", None, 30, 1.0);
+        draw_text("OK", button.center().x - ok_size.width / 2.0, button.y + 29.0, 30.0, WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: i32) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            level: 2,
+            date: "2024-02-29".to_string(),
+        }
+    }
+
+    #[test]
+    fn the_table_keeps_the_ten_best_in_order() {
+        let mut scores = HighScores::default();
+        assert!(!scores.qualifies(0));
+        for score in 1..=MAX_ENTRIES as i32 {
+            assert_eq!(scores.insert(entry("A", score * 10)), Some(0));
+        }
+        assert!(!scores.qualifies(10));
+        assert_eq!(scores.insert(entry("B", 5)), None);
+
+        // Ties go below the older entry.
+        assert_eq!(scores.insert(entry("C", 50)), Some(6));
+        assert_eq!(scores.entries.len(), MAX_ENTRIES);
+        assert_eq!(scores.entries[5].name, "A");
+        assert_eq!(scores.entries.last().unwrap().score, 20);
+        assert!(scores.entries.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn the_table_round_trips_through_text() {
+        let mut scores = HighScores::default();
+        scores.insert(entry("ANNE MARIE", 420));
+        scores.insert(entry("BOB", 1200));
+        assert_eq!(HighScores::parse(&scores.to_text()), Ok(scores));
+
+        assert_eq!(HighScores::parse("# best games\n\n10\t1\t2024-01-01\tX\n").unwrap().entries.len(), 1);
+        assert_eq!(
+            HighScores::parse("10\t1\t2024-01-01\tX\nlots\t1\t2024-01-01\tY").unwrap_err(),
+            "line 2: score must be a number"
+        );
+        assert!(HighScores::parse("10 1 2024-01-01 X").is_err());
+    }
+
+    #[test]
+    fn missing_tables_load_empty_and_saving_creates_the_directory() {
+        let dir = std::env::temp_dir().join(format!("arkanoid-scores-{}", std::process::id()));
+        let path = dir.join("nested").join(FILE_NAME);
+        assert_eq!(HighScores::load(&path), HighScores::default());
+
+        let mut scores = HighScores::default();
+        scores.insert(entry("ZED", 90));
+        scores.save(&path).unwrap();
+        assert_eq!(HighScores::load(&path), scores);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dates_are_calendar_days() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_709_251_199), "2024-02-29");
+        assert_eq!(date(1_735_689_600), "2025-01-01");
+    }
+
+    #[test]
+    fn names_are_short_and_tidy() {
+        let mut entry = NameEntry::default();
+        for character in " ann\t-é marie-louise".chars() {
+            entry.type_char(character);
+        }
+        assert_eq!(entry.name, "ANN- MARIE-L");
+        assert_eq!(NameEntry::default().finish(), DEFAULT_NAME);
+    }
+}
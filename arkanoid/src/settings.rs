@@ -0,0 +1,430 @@
+//! Player settings and the screen that changes them, opened with S while
+//! the ball waits to be served.
+//!
+//! The paddle can be steered with the arrow keys, the mouse or a touch
+//! screen. With the mouse or touch the paddle's center follows the pointer,
+//! and a click or tap does what Space does; smoothing makes the paddle
+//! glide after the pointer instead of jumping to it.
+//!
+//! Visual effects, the particles, ball trail and screen shake, can be
+//! turned off here too, and the volume of the sound effects and music set.
+//!
+//! The settings are saved when the screen is closed, one `key=value` line
+//! each, to `settings.txt` in the user's config directory
+//! (`$XDG_CONFIG_HOME/arkanoid` on Linux).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use macroquad::prelude::*;
+
+const FILE_NAME: &str = "settings.txt";
+/// Where the settings go on systems without a config directory.
+const FALLBACK_FILE: &str = "arkanoid_settings.txt";
+
+/// How quickly a smoothed paddle catches up with the pointer: the share of
+/// the gap closed in a second is `1 - e^-SMOOTHING_RATE`.
+const SMOOTHING_RATE: f32 = 15.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Control {
+    Keyboard,
+    Mouse,
+    Touch,
+}
+
+impl Control {
+    const ALL: [Control; 3] = [Control::Keyboard, Control::Mouse, Control::Touch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Control::Keyboard => "KEYBOARD",
+            Control::Mouse => "MOUSE",
+            Control::Touch => "TOUCH",
+        }
+    }
+
+    /// What the player does in place of pressing Space.
+    pub fn action_name(self) -> &'static str {
+        match self {
+            Control::Keyboard => "PRESS SPACE",
+            Control::Mouse => "CLICK",
+            Control::Touch => "TAP",
+        }
+    }
+
+    pub fn instructions(self) -> &'static str {
+        match self {
+            Control::Keyboard => "CONTROLS: LEFT/RIGHT ARROWS",
+            Control::Mouse => "CONTROLS: MOVE THE MOUSE",
+            Control::Touch => "CONTROLS: DRAG YOUR FINGER",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Control::Keyboard => "keyboard",
+            Control::Mouse => "mouse",
+            Control::Touch => "touch",
+        }
+    }
+
+    fn cycle(self, step: isize) -> Control {
+        let index = Control::ALL.iter().position(|&control| control == self).unwrap_or(0);
+        let len = Control::ALL.len() as isize;
+        Control::ALL[(index as isize + step).rem_euclid(len) as usize]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Settings {
+    pub control: Control,
+    pub smoothing: bool,
+    /// Particles, the ball trail and screen shake.
+    pub visual_effects: bool,
+    /// Volumes from 0 to 1. The sound effects and music are each played at
+    /// their own volume times the master volume.
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            control: Control::Keyboard,
+            smoothing: false,
+            visual_effects: true,
+            master_volume: 0.8,
+            sfx_volume: 1.0,
+            music_volume: 0.5,
+        }
+    }
+}
+
+impl Settings {
+    /// Where the pointer wants the paddle's center, if the paddle follows
+    /// a pointer.
+    pub fn pointer_x(&self) -> Option<f32> {
+        match self.control {
+            Control::Keyboard => None,
+            Control::Mouse => Some(mouse_position().0),
+            Control::Touch => touches().first().map(|touch| touch.position.x),
+        }
+    }
+
+    /// Whether the player served, released the ball or fired this frame.
+    /// Space always works, whatever the control.
+    pub fn action_pressed(&self) -> bool {
+        let pointer = match self.control {
+            Control::Keyboard => false,
+            Control::Mouse => is_mouse_button_pressed(MouseButton::Left),
+            Control::Touch => touches().iter().any(|touch| touch.phase == TouchPhase::Started),
+        };
+        pointer || is_key_pressed(KeyCode::Space)
+    }
+
+    /// Moves a paddle centered at `center` toward the pointer at `target`
+    /// over `seconds`.
+    pub fn follow(&self, center: f32, target: f32, seconds: f32) -> f32 {
+        if !self.smoothing {
+            return target;
+        }
+        center + (target - center) * (1.0 - (-SMOOTHING_RATE * seconds).exp())
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let on_off = |on: bool| if on { "ON" } else { "OFF" };
+        vec![
+            format!("CONTROL: {}", self.control.name()),
+            format!("SMOOTHING: {}", on_off(self.smoothing)),
+            format!("EFFECTS: {}", on_off(self.visual_effects)),
+            format!("MASTER VOLUME: {}%", percent(self.master_volume)),
+            format!("EFFECTS VOLUME: {}%", percent(self.sfx_volume)),
+            format!("MUSIC VOLUME: {}%", percent(self.music_volume)),
+            "BACK".to_string(),
+        ]
+    }
+
+    /// Changes setting `line` by `step`. Returns false for the BACK line.
+    fn adjust(&mut self, line: usize, step: isize) -> bool {
+        // Volumes go up and down in tenths, wrapping around.
+        let step_volume = |volume: &mut f32| {
+            let tenths = (*volume * 10.0).round() as isize;
+            *volume = (tenths + step).rem_euclid(11) as f32 / 10.0;
+        };
+        match line {
+            0 => self.control = self.control.cycle(step),
+            1 => self.smoothing = !self.smoothing,
+            2 => self.visual_effects = !self.visual_effects,
+            3 => step_volume(&mut self.master_volume),
+            4 => step_volume(&mut self.sfx_volume),
+            5 => step_volume(&mut self.music_volume),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn to_text(self) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        format!(
+            "control={}\nsmoothing={}\neffects={}\nmaster_volume={}\nsfx_volume={}\nmusic_volume={}\n",
+            self.control.key(),
+            on_off(self.smoothing),
+            on_off(self.visual_effects),
+            percent(self.master_volume),
+            percent(self.sfx_volume),
+            percent(self.music_volume),
+        )
+    }
+
+    /// Reads settings saved by `to_text`. Settings missing from the text
+    /// keep their defaults, so files from older versions still load.
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+            let (key, value) = line.split_once('=').ok_or_else(|| error("expected key=value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let on_off = || match value {
+                "on" => Ok(true),
+                "off" => Ok(false),
+                _ => Err(error(&format!("{} must be on or off", key))),
+            };
+            let volume = || match value.parse::<u32>() {
+                Ok(percent) if percent <= 100 => Ok(percent as f32 / 100.0),
+                _ => Err(error(&format!("{} must be a percentage from 0 to 100", key))),
+            };
+            match key {
+                "control" => {
+                    settings.control = Control::ALL
+                        .into_iter()
+                        .find(|control| control.key() == value)
+                        .ok_or_else(|| error("control must be keyboard, mouse or touch"))?
+                }
+                "smoothing" => settings.smoothing = on_off()?,
+                "effects" => settings.visual_effects = on_off()?,
+                "master_volume" => settings.master_volume = volume()?,
+                "sfx_volume" => settings.sfx_volume = volume()?,
+                "music_volume" => settings.music_volume = volume()?,
+                _ => return Err(error(&format!("unknown setting '{}'", key))),
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Where the settings are kept: the user's config directory, or the
+    /// current directory if the system has none.
+    pub fn path() -> PathBuf {
+        match dirs::config_dir() {
+            Some(dir) => dir.join("arkanoid").join(FILE_NAME),
+            None => PathBuf::from(FALLBACK_FILE),
+        }
+    }
+
+    /// Reads the settings saved at `path`. A missing or unreadable file is
+    /// not fatal: a warning is printed and the defaults are used.
+    pub fn load(path: &Path) -> Settings {
+        match fs::read_to_string(path) {
+            Ok(text) => Settings::parse(&text).unwrap_or_else(|e| {
+                eprintln!("Settings file {} is invalid, {}; using the defaults", path.display(), e);
+                Settings::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                eprintln!("Settings load failed: {}, using the defaults", e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+}
+
+fn percent(volume: f32) -> u32 {
+    (volume * 100.0).round() as u32
+}
+
+const MENU_TOP: f32 = 170.0;
+const MENU_LINE_HEIGHT: f32 = 46.0;
+
+/// The settings screen, drawn over the game.
+#[derive(Default)]
+pub struct SettingsMenu {
+    pub open: bool,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.selected = 0;
+    }
+
+    /// Handles this frame's keys and clicks.
+    pub fn update(&mut self, settings: &mut Settings, screen_width: f32) {
+        let lines = settings.lines().len();
+        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::S) {
+            self.open = false;
+            return;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + lines - 1) % lines;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % lines;
+        }
+        if is_key_pressed(KeyCode::Left) {
+            settings.adjust(self.selected, -1);
+        }
+        if (is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space))
+            && !settings.adjust(self.selected, 1)
+        {
+            self.open = false;
+        }
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (x, y) = mouse_position();
+            let clicked = (0..lines).find(|&line| Self::line_rect(line, screen_width).contains(vec2(x, y)));
+            if let Some(line) = clicked {
+                self.selected = line;
+                if !settings.adjust(line, 1) {
+                    self.open = false;
+                }
+            }
+        }
+    }
+
+    fn line_rect(line: usize, screen_width: f32) -> Rect {
+        Rect::new(
+            screen_width / 2.0 - 200.0,
+            MENU_TOP + line as f32 * MENU_LINE_HEIGHT - 35.0,
+            400.0,
+            MENU_LINE_HEIGHT,
+        )
+    }
+
+    pub fn draw(&self, settings: &Settings, screen_width: f32, screen_height: f32) {
+        draw_rectangle(0.0, 0.0, screen_width, screen_height, Color::new(0.0, 0.0, 0.0, 0.85));
+
+        let title = "SETTINGS";
+        let title_size = measure_text(title, None, 60, 1.0);
+        draw_text(title, screen_width / 2.0 - title_size.width / 2.0, MENU_TOP - 70.0, 60.0, WHITE);
+
+        for (line, text) in settings.lines().iter().enumerate() {
+            let color = if line == self.selected { YELLOW } else { LIGHTGRAY };
+            let size = measure_text(text, None, 36, 1.0);
+            draw_text(
+                text,
+                screen_width / 2.0 - size.width / 2.0,
+                MENU_TOP + line as f32 * MENU_LINE_HEIGHT,
+                36.0,
+                color,
+            );
+        }
+
+        let help = "UP/DOWN TO CHOOSE, LEFT/RIGHT OR CLICK TO CHANGE";
+        let help_size = measure_text(help, None, 24, 1.0);
+        draw_text(help, screen_width / 2.0 - help_size.width / 2.0, screen_height - 60.0, 24.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_cycles_both_ways() {
+        let mut settings = Settings::default();
+        assert!(settings.adjust(0, 1));
+        assert_eq!(settings.control, Control::Mouse);
+        settings.adjust(0, -1);
+        settings.adjust(0, -1);
+        assert_eq!(settings.control, Control::Touch);
+        assert!(settings.adjust(2, 1));
+        assert!(!settings.visual_effects);
+        assert!(!settings.adjust(6, 1));
+    }
+
+    #[test]
+    fn volumes_step_in_tenths_and_wrap() {
+        let mut settings = Settings::default();
+        settings.adjust(3, 1);
+        assert_eq!(settings.master_volume, 0.9);
+        settings.adjust(4, 1);
+        assert_eq!(settings.sfx_volume, 0.0);
+        settings.adjust(5, -1);
+        assert_eq!(settings.music_volume, 0.4);
+    }
+
+    #[test]
+    fn settings_round_trip_through_text() {
+        let settings = Settings {
+            control: Control::Touch,
+            smoothing: true,
+            visual_effects: false,
+            master_volume: 0.3,
+            sfx_volume: 0.0,
+            music_volume: 1.0,
+        };
+        assert_eq!(Settings::parse(&settings.to_text()), Ok(settings));
+        assert_eq!(Settings::parse("# old file\ncontrol=mouse\n").unwrap().music_volume, 0.5);
+    }
+
+    #[test]
+    fn bad_settings_name_the_line() {
+        assert_eq!(Settings::parse("control=joystick"), Err("line 1: control must be keyboard, mouse or touch".to_string()));
+        assert_eq!(Settings::parse("\nsmoothing=yes"), Err("line 2: smoothing must be on or off".to_string()));
+        assert_eq!(
+            Settings::parse("music_volume=110"),
+            Err("line 1: music_volume must be a percentage from 0 to 100".to_string())
+        );
+        assert_eq!(Settings::parse("volume=5"), Err("line 1: unknown setting 'volume'".to_string()));
+        assert!(Settings::parse("effects").is_err());
+    }
+
+    #[test]
+    fn missing_file_loads_the_defaults() {
+        let dir = std::env::temp_dir().join(format!("arkanoid-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Settings::load(&dir.join("missing.txt")), Settings::default());
+
+        // Saving creates the directory it goes in.
+        let path = dir.join("arkanoid").join(FILE_NAME);
+        let settings = Settings { smoothing: true, ..Settings::default() };
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path), settings);
+
+        fs::write(&path, "effects=maybe").unwrap();
+        assert_eq!(Settings::load(&path), Settings::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn smoothing_eases_toward_the_pointer() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.follow(100.0, 300.0, 1.0 / 120.0), 300.0);
+
+        settings.smoothing = true;
+        let mut center = 100.0;
+        for _ in 0..10 {
+            let next = settings.follow(center, 300.0, 1.0 / 120.0);
+            assert!(center < next && next < 300.0);
+            center = next;
+        }
+        for _ in 0..120 {
+            center = settings.follow(center, 300.0, 1.0 / 120.0);
+        }
+        assert!((center - 300.0).abs() < 0.01);
+    }
+}
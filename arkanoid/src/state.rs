@@ -0,0 +1,279 @@
+//! The flow of a game, from the title screen through serving, playing and
+//! pausing to its end. `State::next` only works out where an event leads;
+//! the game does what each change calls for, such as loading the next level.
+
+use macroquad::prelude::*;
+
+/// Seconds the game lingers after a life is lost or a level is cleared
+/// before the ball is served again. Pressing the action skips the wait.
+pub const LIFE_LOST_TIME: f32 = 1.5;
+pub const LEVEL_CLEARED_TIME: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    Title,
+    /// The ball sits on the paddle until it is launched.
+    Serving,
+    Playing,
+    /// Paused while serving or playing; `serving` says which to go back to.
+    Paused { serving: bool },
+    /// Seconds left before the next serve.
+    LifeLost { time: f32 },
+    LevelCleared { time: f32 },
+    GameOver,
+    Victory,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    /// The player's action: start, launch, or skip a wait.
+    Action,
+    /// Escape or P: pauses, or resumes if already paused.
+    Pause,
+    Resume,
+    Restart,
+    /// Back to the title screen.
+    Quit,
+    /// The last ball fell out, leaving `lives`.
+    BallLost { lives: i32 },
+    /// All breakable bricks are gone; `last` if it was the last level.
+    LevelCleared { last: bool },
+    Tick(f32),
+}
+
+impl State {
+    /// The state `event` leads to. Events that mean nothing in this state
+    /// leave it as it is.
+    pub fn next(self, event: Event) -> State {
+        match (self, event) {
+            (State::Title, Event::Action) => State::Serving,
+            (State::Serving, Event::Action) => State::Playing,
+            (State::Serving, Event::Pause) => State::Paused { serving: true },
+            (State::Playing, Event::Pause) => State::Paused { serving: false },
+            (State::Paused { serving }, Event::Pause | Event::Resume) => {
+                if serving {
+                    State::Serving
+                } else {
+                    State::Playing
+                }
+            }
+            (State::Paused { .. }, Event::Restart) => State::Serving,
+            (State::Paused { .. }, Event::Quit) => State::Title,
+            (State::Playing, Event::BallLost { lives }) => {
+                if lives > 0 {
+                    State::LifeLost { time: LIFE_LOST_TIME }
+                } else {
+                    State::GameOver
+                }
+            }
+            (State::Playing, Event::LevelCleared { last }) => {
+                if last {
+                    State::Victory
+                } else {
+                    State::LevelCleared { time: LEVEL_CLEARED_TIME }
+                }
+            }
+            (State::LifeLost { .. } | State::LevelCleared { .. }, Event::Action) => State::Serving,
+            (State::LifeLost { time }, Event::Tick(seconds)) => {
+                if time > seconds {
+                    State::LifeLost { time: time - seconds }
+                } else {
+                    State::Serving
+                }
+            }
+            (State::LevelCleared { time }, Event::Tick(seconds)) => {
+                if time > seconds {
+                    State::LevelCleared { time: time - seconds }
+                } else {
+                    State::Serving
+                }
+            }
+            (State::GameOver | State::Victory, Event::Action) => State::Title,
+            (state, _) => state,
+        }
+    }
+
+    /// Whether the ball is in play and the game is simulated.
+    pub fn is_playing(self) -> bool {
+        self == State::Playing
+    }
+
+    /// Whether this is a screen between games, where the settings and the
+    /// editor can be opened. Serving is part of a game: leaving it for the
+    /// editor would lose the game.
+    pub fn is_between_games(self) -> bool {
+        matches!(self, State::Title | State::GameOver | State::Victory)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PauseChoice {
+    Resume,
+    Restart,
+    Quit,
+}
+
+impl PauseChoice {
+    const ALL: [PauseChoice; 3] = [PauseChoice::Resume, PauseChoice::Restart, PauseChoice::Quit];
+
+    fn name(self) -> &'static str {
+        match self {
+            PauseChoice::Resume => "RESUME",
+            PauseChoice::Restart => "RESTART",
+            PauseChoice::Quit => "QUIT TO TITLE",
+        }
+    }
+
+    pub fn event(self) -> Event {
+        match self {
+            PauseChoice::Resume => Event::Resume,
+            PauseChoice::Restart => Event::Restart,
+            PauseChoice::Quit => Event::Quit,
+        }
+    }
+}
+
+const MENU_TOP: f32 = 260.0;
+const MENU_LINE_HEIGHT: f32 = 56.0;
+
+/// The menu shown while the game is paused.
+#[derive(Default)]
+pub struct PauseMenu {
+    selected: usize,
+}
+
+impl PauseMenu {
+    /// Handles this frame's keys and clicks, returning the choice made.
+    pub fn update(&mut self, screen_width: f32) -> Option<PauseChoice> {
+        let count = PauseChoice::ALL.len();
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + count - 1) % count;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % count;
+        }
+        let mut choice = None;
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space) {
+            choice = Some(PauseChoice::ALL[self.selected]);
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (x, y) = mouse_position();
+            if let Some(line) = (0..count).find(|&line| Self::line_rect(line, screen_width).contains(vec2(x, y))) {
+                choice = Some(PauseChoice::ALL[line]);
+            }
+        }
+        if choice.is_some() {
+            self.selected = 0;
+        }
+        choice
+    }
+
+    fn line_rect(line: usize, screen_width: f32) -> Rect {
+        Rect::new(
+            screen_width / 2.0 - 200.0,
+            MENU_TOP + line as f32 * MENU_LINE_HEIGHT - 40.0,
+            400.0,
+            MENU_LINE_HEIGHT,
+        )
+    }
+
+    pub fn draw(&self, screen_width: f32, screen_height: f32) {
+        draw_rectangle(0.0, 0.0, screen_width, screen_height, Color::new(0.0, 0.0, 0.0, 0.7));
+
+        let title = "PAUSED";
+        let title_size = measure_text(title, None, 60, 1.0);
+        draw_text(title, screen_width / 2.0 - title_size.width / 2.0, MENU_TOP - 90.0, 60.0, WHITE);
+
+        for (line, choice) in PauseChoice::ALL.iter().enumerate() {
+            let color = if line == self.selected { YELLOW } else { LIGHTGRAY };
+            let size = measure_text(choice.name(), None, 40, 1.0);
+            draw_text(
+                choice.name(),
+                screen_width / 2.0 - size.width / 2.0,
+                MENU_TOP + line as f32 * MENU_LINE_HEIGHT,
+                40.0,
+                color,
+            );
+        }
+
+        let help = "ESC OR P TO RESUME";
+        let help_size = measure_text(help, None, 24, 1.0);
+        draw_text(help, screen_width / 2.0 - help_size.width / 2.0, screen_height - 60.0, 24.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut state: State, events: &[Event]) -> State {
+        for &event in events {
+            state = state.next(event);
+        }
+        state
+    }
+
+    #[test]
+    fn a_game_goes_from_title_to_victory() {
+        let state = run(
+            State::Title,
+            &[
+                Event::Action,
+                Event::Action,
+                Event::LevelCleared { last: false },
+                Event::Tick(LEVEL_CLEARED_TIME / 2.0),
+            ],
+        );
+        assert_eq!(state, State::LevelCleared { time: LEVEL_CLEARED_TIME / 2.0 });
+
+        let state = run(state, &[Event::Tick(LEVEL_CLEARED_TIME), Event::Action, Event::LevelCleared { last: true }]);
+        assert_eq!(state, State::Victory);
+        assert_eq!(state.next(Event::Action), State::Title);
+    }
+
+    #[test]
+    fn losing_the_last_life_ends_the_game() {
+        let state = run(State::Playing, &[Event::BallLost { lives: 2 }]);
+        assert_eq!(state, State::LifeLost { time: LIFE_LOST_TIME });
+        // The action skips the wait.
+        assert_eq!(state.next(Event::Action), State::Serving);
+        assert_eq!(run(state, &[Event::Tick(LIFE_LOST_TIME)]), State::Serving);
+
+        assert_eq!(State::Playing.next(Event::BallLost { lives: 0 }), State::GameOver);
+    }
+
+    #[test]
+    fn pausing_returns_to_where_the_game_was() {
+        assert_eq!(run(State::Playing, &[Event::Pause, Event::Tick(10.0), Event::Action]), State::Paused { serving: false });
+        assert_eq!(run(State::Playing, &[Event::Pause, Event::Pause]), State::Playing);
+        assert_eq!(run(State::Serving, &[Event::Pause, Event::Resume]), State::Serving);
+        assert_eq!(run(State::Playing, &[Event::Pause, Event::Restart]), State::Serving);
+        assert_eq!(run(State::Playing, &[Event::Pause, Event::Quit]), State::Title);
+    }
+
+    #[test]
+    fn events_out_of_place_change_nothing() {
+        for state in [State::Title, State::GameOver, State::Victory, State::LifeLost { time: 1.0 }] {
+            for event in [Event::Pause, Event::Restart, Event::Quit, Event::BallLost { lives: 0 }, Event::LevelCleared { last: true }] {
+                assert_eq!(state.next(event), state, "{:?} on {:?}", event, state);
+            }
+        }
+        assert_eq!(State::Title.next(Event::Tick(100.0)), State::Title);
+    }
+
+    #[test]
+    fn only_title_and_game_end_are_between_games() {
+        for state in [State::Title, State::GameOver, State::Victory] {
+            assert!(state.is_between_games(), "{:?}", state);
+        }
+        for state in [
+            State::Serving,
+            State::Playing,
+            State::Paused { serving: true },
+            State::LifeLost { time: 1.0 },
+            State::LevelCleared { time: 1.0 },
+        ] {
+            assert!(!state.is_between_games(), "{:?}", state);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use macroquad::rand;
+
+use tictactoe::{CellState, Game, GameState, Player};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Difficulty {
+    Random,
+    Greedy,
+    Perfect,
+}
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Random => "RANDOM",
+            Difficulty::Greedy => "GREEDY",
+            Difficulty::Perfect => "PERFECT",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        [Difficulty::Random, Difficulty::Greedy, Difficulty::Perfect]
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// A way of picking moves, so strategies can be played against each other
+/// in a tournament.
+pub trait Strategy {
+    /// Lower-case name used on the command line and in results.
+    fn name(&self) -> String;
+
+    /// The move to play in `game`, or `None` if there is none.
+    fn next_move(&mut self, game: &Game) -> Option<AiMove>;
+}
+
+impl Strategy for Difficulty {
+    fn name(&self) -> String {
+        Difficulty::name(*self).to_lowercase()
+    }
+
+    fn next_move(&mut self, game: &Game) -> Option<AiMove> {
+        choose_move(game, *self)
+    }
+}
+
+/// Plays the first open cell, reading row by row: a baseline any real
+/// strategy should beat.
+pub struct FirstEmpty;
+
+impl Strategy for FirstEmpty {
+    fn name(&self) -> String {
+        "first-empty".to_string()
+    }
+
+    fn next_move(&mut self, game: &Game) -> Option<AiMove> {
+        if !matches!(game.game_state, GameState::Playing) {
+            return None;
+        }
+        let &(row, col) = empty_cells(game).first()?;
+        Some((row, col, game.marks()[0]))
+    }
+}
+
+/// The strategy called `name`: `first-empty` or an AI difficulty.
+pub fn strategy_by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    if name == FirstEmpty.name() {
+        return Some(Box::new(FirstEmpty));
+    }
+    Difficulty::from_name(name).map(|difficulty| Box::new(difficulty) as Box<dyn Strategy>)
+}
+
+const WIN_SCORE: i32 = 1_000_000;
+/// Positions with at most this many moves are searched to the end. A move is
+/// a cell and a mark, so rules that let players pick a mark search fewer
+/// empty cells to the end.
+const FULL_SEARCH_MOVES: usize = 9;
+/// Search depth used on larger boards, where the full tree is out of reach.
+const LIMITED_SEARCH_DEPTH: i32 = 2;
+
+/// A move for the AI to play: the cell and the mark to put there.
+pub type AiMove = (usize, usize, Player);
+
+pub fn choose_move(game: &Game, difficulty: Difficulty) -> Option<AiMove> {
+    if !matches!(game.game_state, GameState::Playing) {
+        return None;
+    }
+
+    match difficulty {
+        Difficulty::Random => random_move(game),
+        Difficulty::Greedy => greedy_move(game),
+        Difficulty::Perfect => perfect_move(game),
+    }
+}
+
+/// The cells the rules let the player to move fill.
+fn empty_cells(game: &Game) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    for row in 0..game.size() {
+        for col in 0..game.size() {
+            if game.is_open(row, col) {
+                cells.push((row, col));
+            }
+        }
+    }
+    cells
+}
+
+/// Every cell of `cells` with every mark the player to move may use.
+fn moves_on(game: &Game, cells: &[(usize, usize)]) -> Vec<AiMove> {
+    let marks = game.marks();
+    cells
+        .iter()
+        .flat_map(|&(row, col)| marks.iter().map(move |&mark| (row, col, mark)))
+        .collect()
+}
+
+fn random_move(game: &Game) -> Option<AiMove> {
+    let moves = moves_on(game, &empty_cells(game));
+    if moves.is_empty() {
+        return None;
+    }
+    Some(moves[rand::gen_range(0, moves.len())])
+}
+
+/// Takes a winning move if there is one, otherwise blocks the opponent's
+/// winning move, otherwise prefers the center and then the corners.
+fn greedy_move(game: &Game) -> Option<AiMove> {
+    let cells = empty_cells(game);
+    let player = game.current_player;
+    let mark = game.marks()[0];
+
+    for (row, col, mark) in moves_on(game, &cells) {
+        let mut next = game.clone();
+        next.make_move_as(row, col, mark);
+        if matches!(next.game_state, GameState::Win(p, _) if p == player) {
+            return Some((row, col, mark));
+        }
+    }
+
+    let mut opponent = game.clone();
+    opponent.switch_player();
+    for (row, col, their_mark) in moves_on(&opponent, &cells) {
+        let mut next = opponent.clone();
+        next.make_move_as(row, col, their_mark);
+        if matches!(next.game_state, GameState::Win(p, _) if p != player) {
+            return Some((row, col, mark));
+        }
+    }
+
+    let last = game.board.len() - 1;
+    let center = last / 2;
+    if cells.contains(&(center, center)) {
+        return Some((center, center, mark));
+    }
+
+    let corners: Vec<(usize, usize)> = [(0, 0), (0, last), (last, 0), (last, last)]
+        .into_iter()
+        .filter(|cell| cells.contains(cell))
+        .collect();
+    if !corners.is_empty() {
+        let (row, col) = corners[rand::gen_range(0, corners.len())];
+        return Some((row, col, mark));
+    }
+
+    random_move(game)
+}
+
+/// Exact minimax on boards small enough to search to the end; on bigger
+/// (gomoku-style) boards it looks a few moves ahead and scores the
+/// resulting positions with [`evaluate`].
+fn perfect_move(game: &Game) -> Option<AiMove> {
+    let player = game.current_player;
+    let max_depth = if moves_on(game, &empty_cells(game)).len() <= FULL_SEARCH_MOVES {
+        i32::MAX
+    } else {
+        LIMITED_SEARCH_DEPTH
+    };
+    let mut best: Option<(AiMove, i32)> = None;
+
+    for (row, col, mark) in moves_on(game, &candidate_cells(game)) {
+        let mut next = game.clone();
+        if !next.make_move_as(row, col, mark) {
+            continue;
+        }
+        let score = minimax(&next, player, 1, max_depth, -WIN_SCORE - 1, WIN_SCORE + 1);
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some(((row, col, mark), score));
+        }
+    }
+
+    best.map(|(chosen, _)| chosen)
+}
+
+/// Moves worth searching: every empty cell on small boards, otherwise only
+/// the empty cells touching a piece that is already on the board.
+fn candidate_cells(game: &Game) -> Vec<(usize, usize)> {
+    let cells = empty_cells(game);
+    if cells.len() <= FULL_SEARCH_MOVES {
+        return cells;
+    }
+
+    let size = game.size();
+    let near_piece = |&(row, col): &(usize, usize)| {
+        (row.saturating_sub(1)..=(row + 1).min(size - 1)).any(|r| {
+            (col.saturating_sub(1)..=(col + 1).min(size - 1))
+                .any(|c| matches!(game.board[r][c], CellState::Occupied(_)))
+        })
+    };
+    let candidates: Vec<(usize, usize)> = cells.iter().copied().filter(near_piece).collect();
+
+    if candidates.is_empty() {
+        vec![(size / 2, size / 2)]
+    } else {
+        candidates
+    }
+}
+
+/// Scores `game` from the point of view of `player`. Wins found sooner score
+/// higher and losses found later score higher, so the AI wins as fast as
+/// possible and holds out as long as possible when it cannot.
+fn minimax(
+    game: &Game,
+    player: Player,
+    depth: i32,
+    max_depth: i32,
+    mut alpha: i32,
+    mut beta: i32,
+) -> i32 {
+    match game.game_state {
+        GameState::Win(winner, _) if winner == player => return WIN_SCORE - depth,
+        GameState::Win(_, _) => return depth - WIN_SCORE,
+        GameState::Draw => return 0,
+        GameState::Timeout(loser) if loser == player => return depth - WIN_SCORE,
+        GameState::Timeout(_) => return WIN_SCORE - depth,
+        GameState::Playing => {}
+    }
+
+    if depth >= max_depth {
+        return evaluate(game, player);
+    }
+
+    let maximizing = game.current_player == player;
+    let mut best = if maximizing { i32::MIN } else { i32::MAX };
+
+    for (row, col, mark) in moves_on(game, &candidate_cells(game)) {
+        let mut next = game.clone();
+        next.make_move_as(row, col, mark);
+        let score = minimax(&next, player, depth + 1, max_depth, alpha, beta);
+
+        if maximizing {
+            best = best.max(score);
+            alpha = alpha.max(score);
+        } else {
+            best = best.min(score);
+            beta = beta.min(score);
+        }
+        if beta <= alpha {
+            break;
+        }
+    }
+
+    best
+}
+
+/// Heuristic score for an unfinished position: every line segment of
+/// `win_length` cells that only one player has pieces in counts for that
+/// player, weighted by the square of how many pieces it holds. Under rules
+/// where lines lose the count is held against the player instead, and
+/// where marks belong to nobody there is nothing to count.
+fn evaluate(game: &Game, player: Player) -> i32 {
+    if !game.rules.players_own_marks() {
+        return 0;
+    }
+    let sign = if game.rules.line_wins() { 1 } else { -1 };
+    let size = game.size() as isize;
+    let length = game.win_length as isize;
+    let mut score = 0;
+
+    for row in 0..size {
+        for col in 0..size {
+            for (d_row, d_col) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                let end_row = row + d_row * (length - 1);
+                let end_col = col + d_col * (length - 1);
+                if end_row >= size || end_col < 0 || end_col >= size {
+                    continue;
+                }
+
+                let (mut mine, mut theirs) = (0, 0);
+                for i in 0..length {
+                    match game.board[(row + d_row * i) as usize][(col + d_col * i) as usize] {
+                        CellState::Occupied(p) if p == player => mine += 1,
+                        CellState::Occupied(_) => theirs += 1,
+                        CellState::Empty => {}
+                    }
+                }
+
+                if theirs == 0 {
+                    score += mine * mine;
+                } else if mine == 0 {
+                    score -= theirs * theirs;
+                }
+            }
+        }
+    }
+
+    score * sign
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tictactoe::rules::{Misere, RULE_SETS};
+
+    /// Plain negamax without pruning, used as a reference for the AI.
+    /// Returns 1 for a win, 0 for a draw and -1 for a loss for the player to move.
+    fn outcome(game: &Game) -> i32 {
+        match game.game_state {
+            GameState::Win(winner, _) if winner == game.current_player => return 1,
+            GameState::Win(_, _) => return -1,
+            GameState::Draw => return 0,
+            GameState::Timeout(_) => unreachable!("searched games are not timed"),
+            GameState::Playing => {}
+        }
+
+        empty_cells(game)
+            .into_iter()
+            .map(|(row, col)| {
+                let mut next = game.clone();
+                next.make_move(row, col);
+                -outcome(&next)
+            })
+            .max()
+            .unwrap()
+    }
+
+    /// Lets `ai_player` answer every possible sequence of opponent moves and
+    /// asserts it never loses.
+    fn assert_never_loses(game: &Game, ai_player: Player) {
+        match game.game_state {
+            GameState::Win(winner, _) => {
+                assert_eq!(winner, ai_player, "perfect AI lost a game");
+                return;
+            }
+            GameState::Draw => return,
+            GameState::Timeout(_) => unreachable!("searched games are not timed"),
+            GameState::Playing => {}
+        }
+
+        if game.current_player == ai_player {
+            let (row, col, mark) = choose_move(game, Difficulty::Perfect).unwrap();
+            let mut next = game.clone();
+            assert!(next.make_move_as(row, col, mark), "perfect AI played an illegal move");
+            assert_never_loses(&next, ai_player);
+        } else {
+            for (row, col) in empty_cells(game) {
+                let mut next = game.clone();
+                next.make_move(row, col);
+                assert_never_loses(&next, ai_player);
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_ai_never_loses_as_o() {
+        assert_never_loses(&Game::new(3, 3), Player::O);
+    }
+
+    #[test]
+    fn perfect_ai_never_loses_as_x() {
+        assert_never_loses(&Game::new(3, 3), Player::X);
+    }
+
+    #[test]
+    fn perfect_ai_never_loses_misere_as_x() {
+        assert_never_loses(&Game::with_rules(3, 3, &Misere), Player::X);
+    }
+
+    #[test]
+    fn perfect_ai_keeps_best_outcome_from_every_reachable_position() {
+        fn visit(game: &Game) {
+            if !matches!(game.game_state, GameState::Playing) {
+                return;
+            }
+
+            let (row, col, mark) = choose_move(game, Difficulty::Perfect).unwrap();
+            let mut next = game.clone();
+            assert!(next.make_move_as(row, col, mark));
+            assert_eq!(-outcome(&next), outcome(game));
+
+            for (row, col) in empty_cells(game) {
+                let mut next = game.clone();
+                next.make_move(row, col);
+                visit(&next);
+            }
+        }
+
+        visit(&Game::new(3, 3));
+    }
+
+    #[test]
+    fn greedy_ai_blocks_opponent() {
+        let mut game = Game::new(3, 3);
+        game.make_move(0, 0);
+        game.make_move(1, 1);
+        game.make_move(0, 1);
+        assert_eq!(choose_move(&game, Difficulty::Greedy), Some((0, 2, Player::O)));
+    }
+
+    #[test]
+    fn greedy_ai_prefers_winning_to_blocking() {
+        let mut game = Game::new(3, 3);
+        game.make_move(0, 0);
+        game.make_move(1, 0);
+        game.make_move(0, 1);
+        game.make_move(1, 1);
+        assert_eq!(choose_move(&game, Difficulty::Greedy), Some((0, 2, Player::X)));
+    }
+
+    #[test]
+    fn ai_only_plays_open_cells() {
+        for difficulty in [Difficulty::Random, Difficulty::Greedy, Difficulty::Perfect] {
+            for (size, win_length) in [(3, 3), (6, 4)] {
+                let mut game = Game::new(size, win_length);
+                while let Some((row, col, mark)) = choose_move(&game, difficulty) {
+                    assert!(game.make_move_as(row, col, mark));
+                }
+                assert!(!matches!(game.game_state, GameState::Playing));
+            }
+        }
+    }
+
+    #[test]
+    fn ai_plays_every_rule_set_to_the_end() {
+        for rules in RULE_SETS {
+            for difficulty in [Difficulty::Random, Difficulty::Greedy, Difficulty::Perfect] {
+                let &(size, win_length) = rules.presets().iter().rfind(|(size, _)| *size <= 6).unwrap();
+                let mut game = Game::with_rules(size, win_length, rules);
+                while let Some((row, col, mark)) = choose_move(&game, difficulty) {
+                    assert!(game.make_move_as(row, col, mark), "{} move refused", rules.name());
+                }
+                assert!(!matches!(game.game_state, GameState::Playing), "{} stalled", rules.name());
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_ai_completes_five_in_a_row_on_large_board() {
+        let mut game = Game::new(15, 5);
+        for col in 3..7 {
+            game.make_move(7, col);
+            game.make_move(0, col * 2);
+        }
+        let chosen = choose_move(&game, Difficulty::Perfect);
+        assert!(chosen == Some((7, 2, Player::X)) || chosen == Some((7, 7, Player::X)));
+    }
+}
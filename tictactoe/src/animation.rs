@@ -0,0 +1,173 @@
+//! Timing for the board animations: new pieces drawing themselves in, the
+//! win line sweeping across the board and the loser's pieces fading out.
+//!
+//! Animations only follow the board they are shown; they never hold up a
+//! move. Each frame [`BoardAnimation::update`] compares the board with the
+//! one it saw last, so pieces placed by clicks, the AI, the network, redo or
+//! replay all animate the same way.
+
+use tictactoe::ultimate::{ULTIMATE_SIZE, UltimateGame};
+use tictactoe::{CellState, GameState};
+
+/// Seconds for a new piece to be drawn in.
+pub const PIECE_DURATION: f32 = 0.2;
+/// Seconds for the win line to reach the far end of the winning run.
+pub const WIN_LINE_DURATION: f32 = 0.4;
+/// Seconds for the loser's pieces to fade once the game is won.
+pub const FADE_DURATION: f32 = 0.6;
+/// How visible the loser's pieces stay after fading, out of 255.
+pub const FADED_ALPHA: f32 = 70.0;
+
+pub struct BoardAnimation {
+    seen: Vec<Vec<CellState>>,
+    /// Seconds since each cell last changed.
+    ages: Vec<Vec<f32>>,
+    /// Seconds since the game was won, while it stays won.
+    won_for: Option<f32>,
+}
+
+impl BoardAnimation {
+    pub fn new() -> Self {
+        Self {
+            seen: Vec::new(),
+            ages: Vec::new(),
+            won_for: None,
+        }
+    }
+
+    /// Advances the animations by `dt` seconds. Cells that changed since the
+    /// last call start animating from the beginning. A board of a different
+    /// size (a new preset or a loaded game) is shown as it is, without
+    /// animating every piece at once.
+    pub fn update(&mut self, board: &[Vec<CellState>], game_state: &GameState, dt: f32) {
+        if self.seen.len() != board.len() {
+            self.seen = board.to_vec();
+            self.ages = vec![vec![f32::INFINITY; board.len()]; board.len()];
+            self.won_for = matches!(game_state, GameState::Win(_, _)).then_some(f32::INFINITY);
+            return;
+        }
+
+        for (row, cells) in board.iter().enumerate() {
+            for (col, &cell) in cells.iter().enumerate() {
+                if self.seen[row][col] != cell {
+                    self.seen[row][col] = cell;
+                    self.ages[row][col] = 0.0;
+                } else {
+                    self.ages[row][col] += dt;
+                }
+            }
+        }
+
+        self.won_for = match (game_state, self.won_for) {
+            (GameState::Win(_, _), Some(won_for)) => Some(won_for + dt),
+            (GameState::Win(_, _), None) => Some(0.0),
+            _ => None,
+        };
+    }
+
+    /// How far the piece at `(row, col)` has been drawn, from 0 to 1.
+    pub fn piece(&self, row: usize, col: usize) -> f32 {
+        let age = self.ages.get(row).and_then(|ages| ages.get(col)).copied();
+        ease_out(age.unwrap_or(f32::INFINITY) / PIECE_DURATION)
+    }
+
+    /// How far the win line has swept from its start, from 0 to 1.
+    pub fn win_line(&self) -> f32 {
+        ease_out(self.won_for.unwrap_or(0.0) / WIN_LINE_DURATION)
+    }
+
+    /// The alpha the loser's pieces are drawn with.
+    pub fn losing_alpha(&self) -> u8 {
+        let faded = ease_out(self.won_for.unwrap_or(0.0) / FADE_DURATION);
+        (255.0 - (255.0 - FADED_ALPHA) * faded) as u8
+    }
+}
+
+/// Animations for an ultimate game: one per small board, and one for the
+/// big board whose cells are the won small boards.
+pub struct UltimateAnimation {
+    pub boards: Vec<Vec<BoardAnimation>>,
+    pub meta: BoardAnimation,
+}
+
+impl UltimateAnimation {
+    pub fn new() -> Self {
+        Self {
+            boards: (0..ULTIMATE_SIZE)
+                .map(|_| (0..ULTIMATE_SIZE).map(|_| BoardAnimation::new()).collect())
+                .collect(),
+            meta: BoardAnimation::new(),
+        }
+    }
+
+    pub fn update(&mut self, ultimate: &UltimateGame, dt: f32) {
+        for (animations, boards) in self.boards.iter_mut().zip(&ultimate.boards) {
+            for (animation, board) in animations.iter_mut().zip(boards) {
+                animation.update(&board.board, &board.game_state, dt);
+            }
+        }
+        self.meta.update(&ultimate.meta.board, ultimate.game_state(), dt);
+    }
+}
+
+/// Maps `t` to 0..1, starting fast and settling gently at the end.
+fn ease_out(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tictactoe::{Game, Player};
+
+    #[test]
+    fn new_piece_draws_in_over_piece_duration() {
+        let mut game = Game::new(3, 3);
+        let mut animation = BoardAnimation::new();
+        animation.update(&game.board, &game.game_state, 0.0);
+
+        game.make_move(1, 1);
+        animation.update(&game.board, &game.game_state, 0.016);
+        assert_eq!(animation.piece(1, 1), 0.0);
+
+        animation.update(&game.board, &game.game_state, PIECE_DURATION / 2.0);
+        assert!(animation.piece(1, 1) > 0.0 && animation.piece(1, 1) < 1.0);
+
+        animation.update(&game.board, &game.game_state, PIECE_DURATION);
+        assert_eq!(animation.piece(1, 1), 1.0);
+    }
+
+    #[test]
+    fn resized_board_is_shown_without_animating() {
+        let mut game = Game::new(9, 5);
+        game.make_move(4, 4);
+        let mut animation = BoardAnimation::new();
+        animation.update(&game.board, &game.game_state, 0.016);
+        assert_eq!(animation.piece(4, 4), 1.0);
+    }
+
+    #[test]
+    fn win_line_sweeps_and_loser_fades_until_undone() {
+        let mut game = Game::new(3, 3);
+        let mut animation = BoardAnimation::new();
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            animation.update(&game.board, &game.game_state, 0.016);
+            game.make_move(row, col);
+        }
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+
+        animation.update(&game.board, &game.game_state, 0.016);
+        assert_eq!(animation.win_line(), 0.0);
+        assert_eq!(animation.losing_alpha(), 255);
+
+        animation.update(&game.board, &game.game_state, WIN_LINE_DURATION.max(FADE_DURATION));
+        assert_eq!(animation.win_line(), 1.0);
+        assert_eq!(animation.losing_alpha(), FADED_ALPHA as u8);
+
+        game.undo();
+        animation.update(&game.board, &game.game_state, 0.016);
+        assert_eq!(animation.win_line(), 0.0);
+        assert_eq!(animation.losing_alpha(), 255);
+    }
+}
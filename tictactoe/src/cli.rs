@@ -0,0 +1,217 @@
+//! Terminal play: `tictactoe --cli` prints the board as text and reads moves
+//! from stdin, so games can be played over SSH or scripted by piping moves in.
+//!
+//! A move is a cell name like `b2` or a row and column counted from 1, like
+//! `2 2`. Under rules that let players pick a mark it may be followed by
+//! `x` or `o`. `undo` takes back a move and `quit` ends the game.
+
+use std::io::{self, BufRead, Write};
+
+use tictactoe::notation::{cell_name, parse_cell};
+use tictactoe::{CellState, Game, GameState, Player};
+
+use crate::ai::{self, Difficulty};
+use crate::player_name;
+
+/// Plays `game` to the end, reading moves from `input` and writing the
+/// board and results to `output`. With an `opponent` the AI plays O.
+/// Returns the game as it stood when play stopped.
+pub fn run(
+    mut game: Game,
+    opponent: Option<Difficulty>,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Game> {
+    let mut lines = input.lines();
+    write!(output, "\n{}", board_text(&game))?;
+
+    loop {
+        match game.game_state {
+            GameState::Win(player, _) => {
+                writeln!(output, "{} wins!", player_name(player))?;
+                return Ok(game);
+            }
+            GameState::Draw => {
+                writeln!(output, "It's a draw.")?;
+                return Ok(game);
+            }
+            GameState::Timeout(player) => {
+                writeln!(output, "{} ran out of time.", player_name(player))?;
+                return Ok(game);
+            }
+            GameState::Playing => {}
+        }
+
+        if let Some(difficulty) = opponent
+            && game.current_player == Player::O
+        {
+            let Some((row, col, mark)) = ai::choose_move(&game, difficulty) else {
+                writeln!(output, "O has no move to play. Game abandoned.")?;
+                return Ok(game);
+            };
+            game.make_move_as(row, col, mark);
+            writeln!(output, "O plays {}", cell_name(row, col))?;
+            write!(output, "\n{}", board_text(&game))?;
+            continue;
+        }
+
+        write!(output, "{} to move: ", player_name(game.current_player))?;
+        output.flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            writeln!(output)?;
+            writeln!(output, "Game abandoned.")?;
+            return Ok(game);
+        };
+
+        match line.trim() {
+            "" => continue,
+            "quit" => {
+                writeln!(output, "Game abandoned.")?;
+                return Ok(game);
+            }
+            "undo" => {
+                // Against the AI, take back the whole round.
+                if game.undo() && opponent.is_some() && game.current_player == Player::O {
+                    game.undo();
+                }
+                write!(output, "\n{}", board_text(&game))?;
+            }
+            text => match parse_move(text, &game) {
+                Ok((row, col, mark)) => {
+                    if game.make_move_as(row, col, mark) {
+                        write!(output, "\n{}", board_text(&game))?;
+                    } else {
+                        writeln!(output, "{} cannot be played", cell_name(row, col))?;
+                    }
+                }
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+        }
+    }
+}
+
+/// Reads `b2` or `2 2`, optionally followed by the mark to put down.
+fn parse_move(text: &str, game: &Game) -> Result<(usize, usize, Player), String> {
+    let mut tokens: Vec<&str> = text.split_whitespace().collect();
+    let mut mark = game.marks()[0];
+    if tokens.len() > 1 {
+        let picked = match tokens[tokens.len() - 1].to_ascii_lowercase().as_str() {
+            "x" => Some(Player::X),
+            "o" => Some(Player::O),
+            _ => None,
+        };
+        if let Some(picked) = picked {
+            if !game.marks().contains(&picked) {
+                return Err(format!("{} cannot put down {}", player_name(game.current_player), player_name(picked)));
+            }
+            mark = picked;
+            tokens.pop();
+        }
+    }
+
+    let size = game.size();
+    let cell = match tokens.as_slice() {
+        [cell] => parse_cell(&cell.to_ascii_lowercase(), size),
+        [row, col] => match (row.parse::<usize>(), col.parse::<usize>()) {
+            (Ok(row), Ok(col)) if (1..=size).contains(&row) && (1..=size).contains(&col) => {
+                Some((row - 1, col - 1))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    let (row, col) = cell.ok_or_else(|| {
+        format!(
+            "invalid move '{}', enter a cell like {} or a row and column like 1 {}",
+            text,
+            cell_name(size - 1, 1),
+            size
+        )
+    })?;
+    Ok((row, col, mark))
+}
+
+/// The board as text, with column letters above and row numbers on the left.
+/// Cells the rules have closed show as `#`.
+pub fn board_text(game: &Game) -> String {
+    let size = game.size();
+    let mut text = String::from("   ");
+    for col in 0..size {
+        text.push(' ');
+        text.push((b'a' + col as u8) as char);
+    }
+    text.push('\n');
+
+    for row in 0..size {
+        text += &format!("{:>3}", row + 1);
+        for col in 0..size {
+            text.push(' ');
+            text.push(match game.board[row][col] {
+                CellState::Occupied(Player::X) => 'X',
+                CellState::Occupied(Player::O) => 'O',
+                CellState::Empty if game.is_open(row, col) => '.',
+                CellState::Empty if matches!(game.game_state, GameState::Playing) => '#',
+                CellState::Empty => '.',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tictactoe::rules::{Notakto, Wild};
+
+    fn play(game: Game, opponent: Option<Difficulty>, input: &str) -> (Game, String) {
+        let mut output = Vec::new();
+        let game = run(game, opponent, input.as_bytes(), &mut output).unwrap();
+        (game, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn scripted_game_prints_board_and_winner() {
+        let (game, output) = play(Game::new(3, 3), None, "b2\n1 1\nc1\na2\n3 1\n");
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+        assert!(output.ends_with("    a b c\n  1 O . X\n  2 O X .\n  3 X . .\nX wins!\n"), "{}", output);
+        assert!(!output.contains("invalid"));
+    }
+
+    #[test]
+    fn bad_and_illegal_moves_are_reported_and_skipped() {
+        let (game, output) = play(Game::new(3, 3), None, "b2\nb2\nz9\n4 1\nhello world\n");
+        assert_eq!(game.history.len(), 1);
+        assert!(output.contains("b2 cannot be played"), "{}", output);
+        assert_eq!(output.matches("invalid move").count(), 3, "{}", output);
+        assert!(output.ends_with("Game abandoned.\n"), "{}", output);
+    }
+
+    #[test]
+    fn undo_and_mark_choice() {
+        let (game, output) = play(Game::with_rules(3, 3, &Wild), None, "a1 o\nb1 O\nundo\nc3 x\n");
+        assert_eq!(game.history.len(), 2);
+        assert!(game.board[0][0] == CellState::Occupied(Player::O));
+        assert!(game.board[2][2] == CellState::Occupied(Player::X));
+        assert!(!output.contains("invalid"), "{}", output);
+
+        let (_, output) = play(Game::new(3, 3), None, "a1 o\n");
+        assert!(output.contains("X cannot put down O"), "{}", output);
+    }
+
+    #[test]
+    fn ai_answers_every_move() {
+        // Trying every cell in turn plays the game out whatever the AI picks.
+        let input = "a1\nb1\nc1\na2\nb2\nc2\na3\nb3\nc3\n";
+        let (game, output) = play(Game::new(3, 3), Some(Difficulty::Perfect), input);
+        assert!(!matches!(game.game_state, GameState::Playing | GameState::Win(Player::X, _)));
+        assert!(output.contains("O plays"), "{}", output);
+    }
+
+    #[test]
+    fn ai_game_on_a_board_notakto_does_not_fit_ends() {
+        let input = "a1\nb1\nc1\nd1\na2\nb2\nc2\nd2\na3\nb3\nc3\nd3\na4\nb4\nc4\nd4\n";
+        let (game, output) = play(Game::with_rules(4, 3, &Notakto), Some(Difficulty::Random), input);
+        assert!(!matches!(game.game_state, GameState::Playing), "{}", output);
+    }
+}
@@ -0,0 +1,191 @@
+//! Time limits. A [`Clock`] keeps a per-move limit, a chess clock for each
+//! player, or both, and ends the game with [`GameState::Timeout`] when the
+//! player to move runs out.
+//!
+//! The clock never reads the time itself: [`Clock::tick`] is handed the
+//! seconds that passed, so play drives it with the frame time and tests
+//! with whatever they like.
+
+use crate::{Game, GameState, Player};
+
+/// Time limits for a game, in seconds; either may be left off.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TimeControl {
+    /// Time for each move.
+    pub per_move: Option<f32>,
+    /// Time for all of a player's moves together.
+    pub total: Option<f32>,
+}
+
+impl TimeControl {
+    pub fn is_timed(&self) -> bool {
+        self.per_move.is_some() || self.total.is_some()
+    }
+}
+
+/// Time controls offered in play, starting with none.
+pub const TIME_CONTROLS: [TimeControl; 4] = [
+    TimeControl { per_move: None, total: None },
+    TimeControl { per_move: Some(10.0), total: None },
+    TimeControl { per_move: None, total: Some(60.0) },
+    TimeControl { per_move: Some(15.0), total: Some(300.0) },
+];
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub control: TimeControl,
+    /// Time left on each player's chess clock, X first.
+    remaining: [f32; 2],
+    /// Time spent on the move being thought about.
+    move_time: f32,
+    /// Moves in the game when the current move started, to notice moves
+    /// being made or undone.
+    moves_seen: usize,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let total = control.total.unwrap_or(0.0);
+        Self {
+            control,
+            remaining: [total, total],
+            move_time: 0.0,
+            moves_seen: 0,
+        }
+    }
+
+    /// Sets both clocks back to the full time, for a new game.
+    pub fn reset(&mut self) {
+        *self = Clock::new(self.control);
+    }
+
+    /// Runs the clock of the player to move in `game` for `seconds`. The
+    /// per-move time starts again whenever a move is made or taken back.
+    /// Nothing runs once the game is over.
+    pub fn tick(&mut self, game: &mut Game, seconds: f32) {
+        if game.history.len() != self.moves_seen {
+            self.moves_seen = game.history.len();
+            self.move_time = 0.0;
+        }
+        if !self.control.is_timed() || !matches!(game.game_state, GameState::Playing) {
+            return;
+        }
+
+        let player = game.current_player;
+        self.move_time += seconds;
+        let remaining = &mut self.remaining[index(player)];
+        *remaining = (*remaining - seconds).max(0.0);
+
+        let out_of_total = self.control.total.is_some() && *remaining <= 0.0;
+        let out_of_move = self.control.per_move.is_some_and(|limit| self.move_time >= limit);
+        if out_of_total || out_of_move {
+            game.game_state = GameState::Timeout(player);
+        }
+    }
+
+    /// Time left on `player`'s chess clock, if there is one.
+    pub fn remaining(&self, player: Player) -> Option<f32> {
+        self.control.total.map(|_| self.remaining[index(player)])
+    }
+
+    /// Time left for the move being thought about, if moves are timed.
+    pub fn move_remaining(&self) -> Option<f32> {
+        self.control.per_move.map(|limit| (limit - self.move_time).max(0.0))
+    }
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PER_MOVE: TimeControl = TimeControl { per_move: Some(5.0), total: None };
+    const TOTAL: TimeControl = TimeControl { per_move: None, total: Some(10.0) };
+
+    #[test]
+    fn untimed_clock_never_runs_out() {
+        let mut game = Game::new(3, 3);
+        let mut clock = Clock::new(TimeControl::default());
+        clock.tick(&mut game, 1_000_000.0);
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert_eq!(clock.remaining(Player::X), None);
+        assert_eq!(clock.move_remaining(), None);
+    }
+
+    #[test]
+    fn per_move_limit_restarts_with_each_move() {
+        let mut game = Game::new(3, 3);
+        let mut clock = Clock::new(PER_MOVE);
+        clock.tick(&mut game, 4.0);
+        assert_eq!(clock.move_remaining(), Some(1.0));
+        game.make_move(1, 1);
+
+        clock.tick(&mut game, 4.0);
+        assert_eq!(clock.move_remaining(), Some(1.0));
+        assert!(matches!(game.game_state, GameState::Playing));
+
+        clock.tick(&mut game, 1.0);
+        assert!(matches!(game.game_state, GameState::Timeout(Player::O)));
+        assert!(!game.make_move(0, 0));
+    }
+
+    #[test]
+    fn chess_clock_only_runs_for_player_to_move() {
+        let mut game = Game::new(3, 3);
+        let mut clock = Clock::new(TOTAL);
+        clock.tick(&mut game, 3.0);
+        game.make_move(1, 1);
+        clock.tick(&mut game, 6.0);
+        game.make_move(0, 0);
+        assert_eq!(clock.remaining(Player::X), Some(7.0));
+        assert_eq!(clock.remaining(Player::O), Some(4.0));
+
+        clock.tick(&mut game, 6.5);
+        game.make_move(2, 2);
+        clock.tick(&mut game, 5.0);
+        assert!(matches!(game.game_state, GameState::Timeout(Player::O)));
+        assert_eq!(clock.remaining(Player::O), Some(0.0));
+        assert_eq!(clock.remaining(Player::X), Some(0.5));
+    }
+
+    #[test]
+    fn losses_on_time_cannot_be_undone() {
+        let mut game = Game::new(3, 3);
+        let mut clock = Clock::new(PER_MOVE);
+        game.make_move(1, 1);
+        game.make_move(0, 0);
+        game.undo();
+        clock.tick(&mut game, 5.0);
+        assert!(matches!(game.game_state, GameState::Timeout(Player::O)));
+
+        assert!(!game.undo());
+        assert!(!game.redo());
+        assert_eq!(game.history.len(), 1);
+        assert_eq!(game.redo_history.len(), 1);
+        clock.tick(&mut game, 1.0);
+        assert!(matches!(game.game_state, GameState::Timeout(Player::O)));
+        assert_eq!(clock.move_remaining(), Some(0.0));
+    }
+
+    #[test]
+    fn finished_game_stops_the_clock() {
+        let mut game = Game::new(3, 3);
+        let mut clock = Clock::new(TOTAL);
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            clock.tick(&mut game, 1.0);
+            game.make_move(row, col);
+        }
+        clock.tick(&mut game, 60.0);
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+        assert_eq!(clock.remaining(Player::O), Some(8.0));
+
+        clock.reset();
+        assert_eq!(clock.remaining(Player::X), Some(10.0));
+    }
+}
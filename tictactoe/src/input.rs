@@ -0,0 +1,108 @@
+//! Keyboard navigation: a cursor moved with the arrow keys or WASD, number
+//! pad placement on 3x3 boards, and focus on the PLAY AGAIN button.
+
+use macroquad::prelude::*;
+
+/// Which input device the player touched last. The board highlight follows it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputMode {
+    Mouse,
+    Keyboard,
+}
+
+/// What the keyboard cursor is on: a board cell or the PLAY AGAIN button
+/// just below the board.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Focus {
+    Cell(usize, usize),
+    PlayAgain,
+}
+
+impl Focus {
+    /// Moves the cursor by `(d_row, d_col)` on a `size`x`size` board. Going
+    /// down from the last row reaches the button, going up leaves it.
+    pub fn step(self, (d_row, d_col): (isize, isize), size: usize) -> Focus {
+        match self.clamped(size) {
+            Focus::Cell(row, col) => {
+                let next_row = row as isize + d_row;
+                if next_row >= size as isize {
+                    return Focus::PlayAgain;
+                }
+                Focus::Cell(
+                    next_row.clamp(0, size as isize - 1) as usize,
+                    (col as isize + d_col).clamp(0, size as isize - 1) as usize,
+                )
+            }
+            Focus::PlayAgain if d_row < 0 => Focus::Cell(size - 1, size / 2),
+            Focus::PlayAgain => Focus::PlayAgain,
+        }
+    }
+
+    /// Keeps the cursor on the board after it shrinks.
+    pub fn clamped(self, size: usize) -> Focus {
+        match self {
+            Focus::Cell(row, col) => Focus::Cell(row.min(size - 1), col.min(size - 1)),
+            Focus::PlayAgain => Focus::PlayAgain,
+        }
+    }
+}
+
+/// The direction pressed this frame with the arrow keys or WASD, as
+/// `(d_row, d_col)`.
+pub fn cursor_step() -> Option<(isize, isize)> {
+    if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::W) {
+        Some((-1, 0))
+    } else if is_key_pressed(KeyCode::Down) || is_key_pressed(KeyCode::S) {
+        Some((1, 0))
+    } else if is_key_pressed(KeyCode::Left) || is_key_pressed(KeyCode::A) {
+        Some((0, -1))
+    } else if is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::D) {
+        Some((0, 1))
+    } else {
+        None
+    }
+}
+
+pub fn confirm_pressed() -> bool {
+    is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) || is_key_pressed(KeyCode::Space)
+}
+
+/// The cell of a 3x3 board picked with the number pad this frame. The keys
+/// are laid out like the board: 7 is the top-left cell and 3 the bottom-right.
+pub fn numpad_cell() -> Option<(usize, usize)> {
+    const KEYS: [(KeyCode, (usize, usize)); 9] = [
+        (KeyCode::Kp7, (0, 0)),
+        (KeyCode::Kp8, (0, 1)),
+        (KeyCode::Kp9, (0, 2)),
+        (KeyCode::Kp4, (1, 0)),
+        (KeyCode::Kp5, (1, 1)),
+        (KeyCode::Kp6, (1, 2)),
+        (KeyCode::Kp1, (2, 0)),
+        (KeyCode::Kp2, (2, 1)),
+        (KeyCode::Kp3, (2, 2)),
+    ];
+    KEYS.iter()
+        .find(|(key, _)| is_key_pressed(*key))
+        .map(|&(_, cell)| cell)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_stays_on_board_and_reaches_button() {
+        assert_eq!(Focus::Cell(0, 0).step((-1, 0), 3), Focus::Cell(0, 0));
+        assert_eq!(Focus::Cell(0, 2).step((0, 1), 3), Focus::Cell(0, 2));
+        assert_eq!(Focus::Cell(1, 1).step((0, -1), 3), Focus::Cell(1, 0));
+        assert_eq!(Focus::Cell(2, 0).step((1, 0), 3), Focus::PlayAgain);
+        assert_eq!(Focus::PlayAgain.step((1, 0), 3), Focus::PlayAgain);
+        assert_eq!(Focus::PlayAgain.step((-1, 0), 15), Focus::Cell(14, 7));
+    }
+
+    #[test]
+    fn cursor_is_pulled_back_onto_smaller_board() {
+        assert_eq!(Focus::Cell(12, 4).clamped(3), Focus::Cell(2, 2));
+        assert_eq!(Focus::Cell(12, 4).step((0, 1), 3), Focus::Cell(2, 2));
+    }
+}
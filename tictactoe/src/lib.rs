@@ -0,0 +1,509 @@
+//! The rules of tic-tac-toe on any board size, with no rendering, so they
+//! can be tested without opening a window.
+
+pub mod clock;
+pub mod notation;
+pub mod rules;
+pub mod solver;
+pub mod ultimate;
+
+use rules::{Rules, Standard};
+
+pub const DEFAULT_BOARD_SIZE: usize = 3;
+pub const DEFAULT_WIN_LENGTH: usize = 3;
+pub const MAX_BOARD_SIZE: usize = 19;
+/// Board size and win length pairs offered in play, starting with the classic game.
+pub const BOARD_PRESETS: [(usize, usize); 3] = [(3, 3), (9, 5), (15, 5)];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Player {
+    X,
+    O,
+}
+
+impl Player {
+    pub fn other(self) -> Player {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CellState {
+    Empty,
+    Occupied(Player),
+}
+
+#[derive(Clone)]
+pub enum GameState {
+    Playing,
+    Win(Player, WinType),
+    Draw,
+    /// The player ran out of time and loses.
+    Timeout(Player),
+}
+
+/// A move: `player` put `mark` on `(row, col)`. The mark is the player's own
+/// unless the rules let players pick.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Move {
+    pub row: usize,
+    pub col: usize,
+    pub player: Player,
+    pub mark: Player,
+}
+
+/// The winning line, from one end cell to the other as `(row, col)`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WinType {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub board: Vec<Vec<CellState>>,
+    pub win_length: usize,
+    pub rules: &'static dyn Rules,
+    pub first_player: Player,
+    pub current_player: Player,
+    pub game_state: GameState,
+    pub history: Vec<Move>,
+    pub redo_history: Vec<Move>,
+    /// The board before the first move of the history: empty, unless the
+    /// game was set up from a position.
+    pub start: Vec<Vec<CellState>>,
+}
+
+impl Game {
+    pub fn new(size: usize, win_length: usize) -> Self {
+        Self::with_rules(size, win_length, &Standard)
+    }
+
+    pub fn with_rules(size: usize, win_length: usize, rules: &'static dyn Rules) -> Self {
+        Self {
+            board: vec![vec![CellState::Empty; size]; size],
+            win_length,
+            rules,
+            first_player: Player::X,
+            current_player: Player::X,
+            game_state: GameState::Playing,
+            history: Vec::new(),
+            redo_history: Vec::new(),
+            start: vec![vec![CellState::Empty; size]; size],
+        }
+    }
+
+    /// A game set up on `board`, as if `first_player` had moved first and
+    /// the players had taken turns since. Undo stops at this position.
+    pub fn from_position(board: Vec<Vec<CellState>>, win_length: usize, rules: &'static dyn Rules, first_player: Player) -> Self {
+        let mut game = Game::with_rules(board.len(), win_length, rules);
+        let occupied: Vec<(usize, usize)> = (0..board.len())
+            .flat_map(|row| (0..board.len()).map(move |col| (row, col)))
+            .filter(|&(row, col)| board[row][col] != CellState::Empty)
+            .collect();
+        let to_move = if occupied.len().is_multiple_of(2) { first_player } else { first_player.other() };
+        game.first_player = first_player;
+        game.board = board.clone();
+        game.start = board;
+
+        // Judge the position as the rules would have after the last move,
+        // which was made by the player not to move. A line anywhere beats a
+        // full board.
+        game.current_player = to_move.other();
+        let states: Vec<GameState> = occupied.iter().map(|&(row, col)| rules.judge(&game, row, col)).collect();
+        game.game_state = states
+            .iter()
+            .find(|state| matches!(state, GameState::Win(..)))
+            .or_else(|| states.iter().find(|state| !matches!(state, GameState::Playing)))
+            .cloned()
+            .unwrap_or(GameState::Playing);
+        game.current_player = to_move;
+        game
+    }
+
+    pub fn size(&self) -> usize {
+        self.board.len()
+    }
+
+    /// The marks the player to move may put down.
+    pub fn marks(&self) -> &'static [Player] {
+        self.rules.marks(self.current_player)
+    }
+
+    /// Whether `(row, col)` can be played now.
+    pub fn is_open(&self, row: usize, col: usize) -> bool {
+        matches!(self.game_state, GameState::Playing)
+            && self.board[row][col] == CellState::Empty
+            && self.rules.is_open(self, row, col)
+    }
+
+    /// Plays `(row, col)` with the first mark the rules allow, which is the
+    /// player's own one unless the rules say otherwise.
+    pub fn make_move(&mut self, row: usize, col: usize) -> bool {
+        let mark = self.marks()[0];
+        self.make_move_as(row, col, mark)
+    }
+
+    pub fn make_move_as(&mut self, row: usize, col: usize, mark: Player) -> bool {
+        if self.place(row, col, mark) {
+            self.redo_history.clear();
+            return true;
+        }
+        false
+    }
+
+    fn place(&mut self, row: usize, col: usize, mark: Player) -> bool {
+        if self.is_open(row, col) && self.marks().contains(&mark) {
+            self.board[row][col] = CellState::Occupied(mark);
+            self.history.push(Move {
+                row,
+                col,
+                player: self.current_player,
+                mark,
+            });
+            self.check_game_state(row, col);
+            self.switch_player();
+            return true;
+        }
+        false
+    }
+
+    /// Takes back the last move. Moves can only be made while the game is
+    /// still being played, so undoing always returns to `GameState::Playing`.
+    /// A loss on time was not caused by a move and cannot be undone.
+    pub fn undo(&mut self) -> bool {
+        if self.timed_out() {
+            return false;
+        }
+        let Some(last) = self.history.pop() else {
+            return false;
+        };
+        self.board[last.row][last.col] = CellState::Empty;
+        self.current_player = last.player;
+        self.game_state = GameState::Playing;
+        self.redo_history.push(last);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if self.timed_out() {
+            return false;
+        }
+        let Some(next) = self.redo_history.pop() else {
+            return false;
+        };
+        self.place(next.row, next.col, next.mark)
+    }
+
+    /// The game as it stood after the first `moves` moves of the history.
+    pub fn position_after(&self, moves: usize) -> Game {
+        let mut game = Game::from_position(self.start.clone(), self.win_length, self.rules, self.first_player);
+        for next in self.history.iter().take(moves) {
+            game.place(next.row, next.col, next.mark);
+        }
+        game
+    }
+
+    pub fn timed_out(&self) -> bool {
+        matches!(self.game_state, GameState::Timeout(_))
+    }
+
+    pub fn switch_player(&mut self) {
+        self.current_player = self.current_player.other();
+    }
+
+    /// Starts the game over with `player` moving first.
+    pub fn restart_with(&mut self, player: Player) {
+        *self = Game::with_rules(self.size(), self.win_length, self.rules);
+        self.first_player = player;
+        self.current_player = player;
+    }
+
+    /// Asks the rules how the game stands after the player to move filled
+    /// `(row, col)`.
+    pub fn check_game_state(&mut self, row: usize, col: usize) {
+        self.game_state = self.rules.judge(self, row, col);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.board.iter().flatten().all(|&cell| cell != CellState::Empty)
+    }
+
+    /// Finds a line of at least `win_length` pieces passing through `(row, col)`.
+    pub(crate) fn line_through(&self, row: usize, col: usize) -> Option<(Player, WinType)> {
+        self.line_within(row, col, (0, 0, self.size()))
+    }
+
+    /// Like [`Game::line_through`], but only counts cells inside `area`, the
+    /// square given as `(top row, left column, side)`.
+    pub(crate) fn line_within(&self, row: usize, col: usize, area: (usize, usize, usize)) -> Option<(Player, WinType)> {
+        let player = match self.board[row][col] {
+            CellState::Occupied(p) => p,
+            _ => return None,
+        };
+
+        for (d_row, d_col) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+            let start = self.run_end((row, col), (-d_row, -d_col), player, area);
+            let end = self.run_end((row, col), (d_row, d_col), player, area);
+            let length = start.0.abs_diff(end.0).max(start.1.abs_diff(end.1)) + 1;
+
+            if length >= self.win_length {
+                return Some((player, WinType { start, end }));
+            }
+        }
+        None
+    }
+
+    /// Walks from `(row, col)` in direction `(d_row, d_col)` and returns the
+    /// last cell of `area` that still belongs to `player`.
+    fn run_end(
+        &self,
+        (mut row, mut col): (usize, usize),
+        (d_row, d_col): (isize, isize),
+        player: Player,
+        (top, left, side): (usize, usize, usize),
+    ) -> (usize, usize) {
+        let bottom = (top + side).min(self.size());
+        let right = (left + side).min(self.size());
+        loop {
+            let next_row = row.checked_add_signed(d_row).filter(|&r| (top..bottom).contains(&r));
+            let next_col = col.checked_add_signed(d_col).filter(|&c| (left..right).contains(&c));
+            match (next_row, next_col) {
+                (Some(r), Some(c)) if self.board[r][c] == CellState::Occupied(player) => {
+                    row = r;
+                    col = c;
+                }
+                _ => return (row, col),
+            }
+        }
+    }
+
+    /// Starts the next round; players take turns at moving first. Changing
+    /// the board or the opponent starts over with `restart_with` instead, so
+    /// only rounds actually played move the turn on.
+    pub fn reset(&mut self) {
+        self.restart_with(self.first_player.other());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn play(game: &mut Game, moves: &[(usize, usize)]) {
+        for &(row, col) in moves {
+            assert!(game.make_move(row, col));
+        }
+    }
+
+    #[test]
+    fn classic_row_win_spans_whole_row() {
+        let mut game = Game::new(3, 3);
+        play(&mut game, &[(1, 0), (0, 0), (1, 2), (0, 1), (1, 1)]);
+        match game.game_state {
+            GameState::Win(Player::X, win) => {
+                assert_eq!(win, WinType { start: (1, 0), end: (1, 2) });
+            }
+            _ => panic!("expected X to win"),
+        }
+    }
+
+    #[test]
+    fn gomoku_anti_diagonal_win_through_last_move() {
+        let mut game = Game::new(15, 5);
+        play(
+            &mut game,
+            &[(2, 12), (0, 0), (3, 11), (0, 1), (5, 9), (0, 3), (6, 8), (0, 5), (4, 10)],
+        );
+        match game.game_state {
+            GameState::Win(Player::X, win) => {
+                assert_eq!(win, WinType { start: (2, 12), end: (6, 8) });
+            }
+            _ => panic!("expected X to win"),
+        }
+    }
+
+    #[test]
+    fn four_in_a_row_does_not_win_when_five_are_needed() {
+        let mut game = Game::new(9, 5);
+        play(&mut game, &[(4, 0), (0, 0), (4, 1), (0, 2), (4, 2), (0, 4), (4, 3)]);
+        assert!(matches!(game.game_state, GameState::Playing));
+    }
+
+    #[test]
+    fn undo_past_win_restores_turn_and_state() {
+        let mut game = Game::new(3, 3);
+        play(&mut game, &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+
+        assert!(game.undo());
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert_eq!(game.current_player, Player::X);
+        assert!(game.board[0][2] == CellState::Empty);
+
+        assert!(game.undo());
+        assert_eq!(game.current_player, Player::O);
+        assert_eq!(game.history.len(), 3);
+    }
+
+    #[test]
+    fn redo_replays_undone_moves_until_new_move() {
+        let mut game = Game::new(3, 3);
+        play(&mut game, &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        game.undo();
+        game.undo();
+
+        assert!(game.redo());
+        assert!(game.redo());
+        assert!(!game.redo());
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+
+        game.undo();
+        play(&mut game, &[(2, 2)]);
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn undo_after_draw_resumes_play() {
+        let mut game = Game::new(3, 3);
+        play(
+            &mut game,
+            &[(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0), (2, 2)],
+        );
+        assert!(game.undo());
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert_eq!(game.current_player, Player::X);
+    }
+
+    #[test]
+    fn position_after_rebuilds_intermediate_positions() {
+        let mut game = Game::new(3, 3);
+        play(&mut game, &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+
+        let start = game.position_after(0);
+        assert!(start.board.iter().flatten().all(|&cell| cell == CellState::Empty));
+
+        let middle = game.position_after(3);
+        assert_eq!(middle.current_player, Player::O);
+        assert!(matches!(middle.game_state, GameState::Playing));
+
+        let end = game.position_after(game.history.len());
+        assert!(matches!(end.game_state, GameState::Win(Player::X, _)));
+    }
+
+    #[test]
+    fn full_board_without_line_is_a_draw() {
+        let mut game = Game::new(3, 3);
+        play(
+            &mut game,
+            &[(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0), (2, 2)],
+        );
+        assert!(matches!(game.game_state, GameState::Draw));
+    }
+
+    /// The eight lines of a 3x3 board, written out by hand so the exhaustive
+    /// test does not lean on `line_through`.
+    const LINES: [[(usize, usize); 3]; 8] = [
+        [(0, 0), (0, 1), (0, 2)],
+        [(1, 0), (1, 1), (1, 2)],
+        [(2, 0), (2, 1), (2, 2)],
+        [(0, 0), (1, 0), (2, 0)],
+        [(0, 1), (1, 1), (2, 1)],
+        [(0, 2), (1, 2), (2, 2)],
+        [(0, 0), (1, 1), (2, 2)],
+        [(0, 2), (1, 1), (2, 0)],
+    ];
+
+    fn line_owner(game: &Game, line: &[(usize, usize); 3]) -> Option<Player> {
+        match line.map(|(row, col)| game.board[row][col]) {
+            [CellState::Occupied(a), CellState::Occupied(b), CellState::Occupied(c)] if a == b && b == c => Some(a),
+            _ => None,
+        }
+    }
+
+    #[derive(Default)]
+    struct Census {
+        positions: HashSet<Vec<Option<Player>>>,
+        x_win_positions: HashSet<Vec<Option<Player>>>,
+        o_win_positions: HashSet<Vec<Option<Player>>>,
+        draw_positions: HashSet<Vec<Option<Player>>>,
+        games: [usize; 3],
+    }
+
+    /// Checks `game` against the hand-written lines, then follows every legal
+    /// move from it.
+    fn explore(game: &Game, census: &mut Census) {
+        let key: Vec<Option<Player>> = game
+            .board
+            .iter()
+            .flatten()
+            .map(|&cell| match cell {
+                CellState::Empty => None,
+                CellState::Occupied(player) => Some(player),
+            })
+            .collect();
+        census.positions.insert(key.clone());
+
+        let owners: Vec<Player> = LINES.iter().filter_map(|line| line_owner(game, line)).collect();
+        let full = key.iter().all(Option::is_some);
+        let last_mover = game.history.last().map(|last| last.player);
+
+        match &game.game_state {
+            GameState::Win(player, win) => {
+                assert_eq!(Some(*player), last_mover, "{}", game.to_notation());
+                assert!(owners.iter().all(|owner| owner == player), "{}", game.to_notation());
+                let line = LINES
+                    .iter()
+                    .find(|line| {
+                        (line[0] == win.start && line[2] == win.end) || (line[0] == win.end && line[2] == win.start)
+                    })
+                    .unwrap_or_else(|| panic!("{:?} is not a line", win));
+                assert_eq!(line_owner(game, line), Some(*player), "{}", game.to_notation());
+                match player {
+                    Player::X => census.x_win_positions.insert(key),
+                    Player::O => census.o_win_positions.insert(key),
+                };
+                census.games[if *player == Player::X { 0 } else { 1 }] += 1;
+            }
+            GameState::Draw => {
+                assert!(owners.is_empty() && full, "{}", game.to_notation());
+                census.draw_positions.insert(key);
+                census.games[2] += 1;
+            }
+            GameState::Timeout(_) => panic!("untimed game timed out: {}", game.to_notation()),
+            GameState::Playing => {
+                assert!(owners.is_empty() && !full, "{}", game.to_notation());
+                assert_ne!(Some(game.current_player), last_mover);
+            }
+        }
+
+        for row in 0..3 {
+            for col in 0..3 {
+                let mut next = game.clone();
+                let moved = next.make_move(row, col);
+                let legal = matches!(game.game_state, GameState::Playing)
+                    && game.board[row][col] == CellState::Empty;
+                assert_eq!(moved, legal, "{} then {:?}", game.to_notation(), (row, col));
+                if moved {
+                    explore(&next, census);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_reachable_3x3_position_is_classified_correctly() {
+        let mut census = Census::default();
+        explore(&Game::new(3, 3), &mut census);
+
+        assert_eq!(census.positions.len(), 5478);
+        assert_eq!(census.x_win_positions.len(), 626);
+        assert_eq!(census.o_win_positions.len(), 316);
+        assert_eq!(census.draw_positions.len(), 16);
+        assert_eq!(census.games, [131_184, 77_904, 46_080]);
+    }
+}
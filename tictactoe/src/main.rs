@@ -1,112 +1,192 @@
 use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+mod ai;
+mod animation;
+mod cli;
+mod input;
+mod net;
+mod stats;
+mod theme;
+mod tournament;
+
+use ai::Difficulty;
+use animation::{BoardAnimation, UltimateAnimation};
+use input::{Focus, InputMode};
+use net::{Message, NetSession, NetStatus};
+use stats::Scoreboard;
+use theme::{PieceStyle, Theme};
+use tictactoe::clock::{Clock, TIME_CONTROLS, TimeControl};
+use tictactoe::rules::{Rules, Standard, next_rules, rules_by_name};
+use tictactoe::solver::{self, Hint, Outcome};
+use tictactoe::ultimate::{ULTIMATE_SIZE, UltimateGame};
+use tictactoe::{
+    CellState, DEFAULT_BOARD_SIZE, DEFAULT_WIN_LENGTH, Game, GameState, MAX_BOARD_SIZE, Player, WinType,
+};
 
-const BOARD_SIZE: usize = 3;
 const CELL_SIZE: f32 = 150.0;
 const BOARD_PADDING: f32 = 40.0;
-const LINE_THICKNESS: f32 = 6.0;
-const WIN_LINE_THICKNESS: f32 = 10.0;
+/// Space between the small boards of ultimate mode, as a share of the board width.
+const ULTIMATE_GAP: f32 = 0.04;
+const AI_MOVE_DELAY: f32 = 0.4;
+const REPLAY_STEP_DELAY: f32 = 0.8;
+const MESSAGE_DURATION: f32 = 3.0;
+const SAVE_FILE: &str = "game.txt";
+const STATS_FILE: &str = "stats.txt";
 
-#[derive(Clone, Copy, PartialEq)]
-enum Player {
-    X,
-    O,
+/// Where `file` is kept: the user's data directory, or the current
+/// directory under a `tictactoe_` prefix if the system has none.
+fn data_path(file: &str) -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join("tictactoe").join(file),
+        None => PathBuf::from(format!("tictactoe_{}", file)),
+    }
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum CellState {
-    Empty,
-    Occupied(Player),
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
 }
 
-enum GameState {
-    Playing,
-    Win(Player, WinType),
-    Draw,
-}
+/// Reads `--size N` and `--win K` from the command line, falling back to
+/// classic 3x3 tic-tac-toe, or to the first preset of `rules` if they
+/// cannot be played on that board.
+fn board_config_from_args(rules: &dyn Rules) -> (usize, usize) {
+    let value_of = |flag: &str| arg_value(flag).and_then(|value| value.parse::<usize>().ok());
 
-enum WinType {
-    Row(usize),
-    Column(usize),
-    Diagonal,
-    AntiDiagonal,
+    let size = value_of("--size")
+        .unwrap_or(DEFAULT_BOARD_SIZE)
+        .clamp(DEFAULT_BOARD_SIZE, MAX_BOARD_SIZE);
+    let win_length = value_of("--win")
+        .unwrap_or(DEFAULT_WIN_LENGTH.min(size))
+        .clamp(DEFAULT_WIN_LENGTH, size);
+    if !rules.fits(size, win_length) {
+        let (preset_size, preset_win_length) = rules.presets()[0];
+        eprintln!(
+            "{} cannot be played on a {}x{} board with lines of {}, playing {}x{}",
+            rules.name(),
+            size,
+            size,
+            win_length,
+            preset_size,
+            preset_size
+        );
+        return (preset_size, preset_win_length);
+    }
+    (size, win_length)
 }
 
-struct Game {
-    board: [[CellState; BOARD_SIZE]; BOARD_SIZE],
-    current_player: Player,
-    game_state: GameState,
+/// Reads `--rules <name>` from the command line, falling back to the
+/// standard rules.
+fn rules_from_args() -> &'static dyn Rules {
+    match arg_value("--rules") {
+        Some(name) => rules_by_name(&name).unwrap_or_else(|| {
+            eprintln!("Unknown rules '{}', playing standard tic-tac-toe", name);
+            &Standard
+        }),
+        None => &Standard,
+    }
 }
 
-impl Game {
-    fn new() -> Self {
-        Self {
-            board: [[CellState::Empty; BOARD_SIZE]; BOARD_SIZE],
-            current_player: Player::X,
-            game_state: GameState::Playing,
-        }
+/// Reads `--ai <random|greedy|perfect>` from the command line: the AI plays
+/// O at that difficulty.
+fn opponent_from_args() -> Option<Difficulty> {
+    let name = arg_value("--ai")?;
+    let difficulty = Difficulty::from_name(&name);
+    if difficulty.is_none() {
+        eprintln!("Unknown AI difficulty '{}', playing against a human", name);
     }
+    difficulty
+}
 
-    fn make_move(&mut self, row: usize, col: usize) {
-        if let GameState::Playing = self.game_state {
-            if let CellState::Empty = self.board[row][col] {
-                self.board[row][col] = CellState::Occupied(self.current_player);
-                self.check_game_state(row, col);
-                self.switch_player();
+/// Reads `--theme <name|file>` from the command line: a built-in theme by
+/// name or a theme file, falling back to the classic theme.
+fn theme_from_args() -> Theme {
+    let Some(value) = arg_value("--theme") else {
+        return Theme::classic();
+    };
+    Theme::by_name(&value).unwrap_or_else(|| {
+        Theme::load(&value).unwrap_or_else(|e| {
+            eprintln!("Theme load failed: {}", e);
+            Theme::classic()
+        })
+    })
+}
+
+/// Reads `--move-time SECONDS` and `--clock SECONDS` from the command line:
+/// a limit on each move and on each player's moves altogether.
+fn time_control_from_args() -> TimeControl {
+    let seconds = |flag: &str| {
+        let value = arg_value(flag)?;
+        match value.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => Some(seconds),
+            _ => {
+                eprintln!("Invalid {} '{}', playing without it", flag, value);
+                None
             }
         }
+    };
+    TimeControl {
+        per_move: seconds("--move-time"),
+        total: seconds("--clock"),
     }
+}
 
-    fn switch_player(&mut self) {
-        self.current_player = match self.current_player {
-            Player::X => Player::O,
-            Player::O => Player::X,
-        };
+fn time_control_name(control: TimeControl) -> String {
+    match (control.total, control.per_move) {
+        (None, None) => "OFF".to_string(),
+        (None, Some(per_move)) => format!("{}S A MOVE", per_move),
+        (Some(total), None) => format!("{} EACH", clock_text(total)),
+        (Some(total), Some(per_move)) => format!("{} EACH, {}S A MOVE", clock_text(total), per_move),
     }
+}
 
-    fn check_game_state(&mut self, row: usize, col: usize) {
-        let player = match self.board[row][col] {
-            CellState::Occupied(p) => p,
-            _ => return,
-        };
-
-        if self.board[row].iter().all(|&cell| {
-            matches!(cell, CellState::Occupied(p) if p == player)
-        }) {
-            self.game_state = GameState::Win(player, WinType::Row(row));
-            return;
-        }
-
-        if (0..BOARD_SIZE).all(|r| {
-            matches!(self.board[r][col], CellState::Occupied(p) if p == player)
-        }) {
-            self.game_state = GameState::Win(player, WinType::Column(col));
-            return;
-        }
+/// Seconds as `m:ss`, or with tenths under ten seconds.
+fn clock_text(seconds: f32) -> String {
+    if seconds < 10.0 {
+        format!("{:.1}", seconds)
+    } else {
+        let seconds = seconds.ceil() as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
 
-        if row == col && (0..BOARD_SIZE).all(|i| {
-            matches!(self.board[i][i], CellState::Occupied(p) if p == player)
-        }) {
-            self.game_state = GameState::Win(player, WinType::Diagonal);
-            return;
-        }
+/// The mark the player to move puts down: the `picked` one if the rules
+/// allow it, otherwise the first one they do.
+fn mark_to_play(game: &Game, picked: Player) -> Player {
+    let marks = game.marks();
+    if marks.contains(&picked) { picked } else { marks[0] }
+}
 
-        if row + col == BOARD_SIZE - 1 && (0..BOARD_SIZE).all(|i| {
-            matches!(self.board[i][BOARD_SIZE - 1 - i], CellState::Occupied(p) if p == player)
-        }) {
-            self.game_state = GameState::Win(player, WinType::AntiDiagonal);
-            return;
-        }
+fn load_game(path: &str) -> Result<Game, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    Game::from_notation(&text).map_err(|e| format!("{}: {}", path, e))
+}
 
-        if self.board.iter().flatten().all(|&cell| {
-            !matches!(cell, CellState::Empty)
-        }) {
-            self.game_state = GameState::Draw;
-        }
+fn save_game(game: &Game, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
     }
+    fs::write(path, game.to_notation() + "\n")
+}
 
-    fn reset(&mut self) {
-        *self = Game::new();
-    }
+fn draw_scoreboard(theme: &Theme, scoreboard: &Scoreboard, y: f32) {
+    let (session, lifetime) = (scoreboard.session, scoreboard.lifetime);
+    draw_centered_text(
+        &format!(
+            "SESSION  X {}  O {}  DRAWS {}      LIFETIME  X {}  O {}  DRAWS {}",
+            session.x_wins, session.o_wins, session.draws,
+            lifetime.x_wins, lifetime.o_wins, lifetime.draws,
+        ),
+        20,
+        y,
+        theme.text,
+    );
 }
 
 fn window_conf() -> Conf {
@@ -119,6 +199,54 @@ fn window_conf() -> Conf {
     }
 }
 
+fn player_name(player: Player) -> &'static str {
+    match player {
+        Player::X => "X",
+        Player::O => "O",
+    }
+}
+
+fn status_text(theme: &Theme, game_state: &GameState, current_player: Player) -> (String, Color) {
+    let status = match game_state {
+        GameState::Playing => format!("Player {}'s turn", player_name(current_player)),
+        GameState::Win(player, _) => format!("Player {} wins!", player_name(*player)),
+        GameState::Draw => "It's a draw!".to_string(),
+        GameState::Timeout(player) => {
+            format!("Player {} ran out of time, {} wins!", player_name(*player), player_name(player.other()))
+        }
+    };
+
+    let status_color = match game_state {
+        GameState::Win(player, _) => theme.player(*player, 255),
+        GameState::Timeout(player) => theme.player(player.other(), 255),
+        _ => theme.info,
+    };
+
+    (status, status_color)
+}
+
+/// Draws each player's time on either side of the status line: their chess
+/// clock, and for the player to move the time left for this move.
+fn draw_clocks(theme: &Theme, clock: &Clock, game: &Game, left: f32, right: f32, y: f32) {
+    for player in [Player::X, Player::O] {
+        let to_move = matches!(game.game_state, GameState::Playing) && game.current_player == player;
+        let mut parts = vec![player_name(player).to_string()];
+        if let Some(remaining) = clock.remaining(player) {
+            parts.push(clock_text(remaining));
+        }
+        if let Some(remaining) = clock.move_remaining().filter(|_| to_move) {
+            parts.push(format!("({})", clock_text(remaining)));
+        }
+        let text = parts.join(" ");
+        let color = if to_move { theme.player(player, 255) } else { theme.info };
+        let x = match player {
+            Player::X => left,
+            Player::O => right - measure_text(&text, None, 28, 1.0).width,
+        };
+        draw_text(&text, x, y, 28.0, color);
+    }
+}
+
 fn draw_centered_text(text: &str, font_size: u16, y: f32, color: Color) {
     let dim = measure_text(text, None, font_size, 1.0);
     draw_text(
@@ -130,173 +258,680 @@ fn draw_centered_text(text: &str, font_size: u16, y: f32, color: Color) {
     );
 }
 
-fn draw_grid_lines(start_x: f32, start_y: f32, size: f32, line_thickness: f32) {
-    for i in 0..=BOARD_SIZE {
+#[allow(clippy::too_many_arguments)]
+fn draw_button(theme: &Theme, text: &str, x: f32, y: f32, width: f32, height: f32, font_size: u16, enabled: bool) -> bool {
+    let (mouse_x, mouse_y) = mouse_position();
+    let hovered = enabled && mouse_x >= x && mouse_x <= x + width &&
+                  mouse_y >= y && mouse_y <= y + height;
+
+    let (button_color, border_color) = if !enabled {
+        (theme.button_disabled, theme.button_disabled_border)
+    } else if hovered {
+        (theme.button_hover, theme.button_border)
+    } else {
+        (theme.button, theme.button_border)
+    };
+
+    draw_rectangle(x, y, width, height, button_color);
+    draw_rectangle_lines(x, y, width, height, 3.0, border_color);
+
+    let dim = measure_text(text, None, font_size, 1.0);
+    draw_text(
+        text,
+        x + (width - dim.width) / 2.0,
+        y + height / 2.0 + font_size as f32 / 3.0,
+        font_size as f32,
+        if enabled { theme.button_text } else { theme.button_disabled_text },
+    );
+
+    hovered
+}
+
+fn draw_grid_lines(start_x: f32, start_y: f32, size: f32, board_size: usize, line_thickness: f32, color: Color) {
+    for i in 0..=board_size {
         let pos = start_x + i as f32 * size;
         draw_line(
             pos,
             start_y,
             pos,
-            start_y + size * BOARD_SIZE as f32,
+            start_y + size * board_size as f32,
             line_thickness,
-            Color::from_rgba(180, 180, 180, 255),
+            color,
         );
         draw_line(
             start_x,
             start_y + i as f32 * size,
-            start_x + size * BOARD_SIZE as f32,
+            start_x + size * board_size as f32,
             start_y + i as f32 * size,
             line_thickness,
-            Color::from_rgba(180, 180, 180, 255),
+            color,
         );
     }
 }
 
+/// Draws the line through the winning run in the color of `mark`, swept
+/// `progress` (0 to 1) of the way from its start.
 fn draw_win_line(
+    theme: &Theme,
     start_x: f32,
     start_y: f32,
-    width: f32,
+    cell_size: f32,
     win_type: &WinType,
-    color: Color,
+    mark: Player,
+    progress: f32,
 ) {
-    match win_type {
-        WinType::Row(row) => {
-            let y = start_y + *row as f32 * CELL_SIZE + CELL_SIZE / 2.0;
-            draw_line(start_x, y, start_x + width, y, WIN_LINE_THICKNESS, color);
-        }
-        WinType::Column(col) => {
-            let x = start_x + *col as f32 * CELL_SIZE + CELL_SIZE / 2.0;
-            draw_line(x, start_y, x, start_y + width, WIN_LINE_THICKNESS, color);
+    let (start_row, start_col) = win_type.start;
+    let (end_row, end_col) = win_type.end;
+    let direction = vec2(
+        (end_col as f32 - start_col as f32).signum(),
+        (end_row as f32 - start_row as f32).signum(),
+    );
+    let overhang = direction * cell_size / 2.0;
+
+    let from = vec2(
+        start_x + start_col as f32 * cell_size + cell_size / 2.0,
+        start_y + start_row as f32 * cell_size + cell_size / 2.0,
+    ) - overhang;
+    let to = vec2(
+        start_x + end_col as f32 * cell_size + cell_size / 2.0,
+        start_y + end_row as f32 * cell_size + cell_size / 2.0,
+    ) + overhang;
+    let to = from + (to - from) * progress;
+
+    draw_line(
+        from.x,
+        from.y,
+        to.x,
+        to.y,
+        theme.win_line_thickness * cell_size / CELL_SIZE,
+        theme.player(mark, 255),
+    );
+}
+
+/// Draws a piece `progress` (0 to 1) of the way in: an X stroke by stroke,
+/// an O as a ring closing clockwise from the top or, in the solid style, a
+/// disc growing from the center.
+#[allow(clippy::too_many_arguments)]
+fn draw_piece(theme: &Theme, player: Player, center_x: f32, center_y: f32, radius: f32, thickness: f32, alpha: u8, progress: f32) {
+    let color = theme.player(player, alpha);
+    match (player, theme.pieces) {
+        (Player::X, style) => {
+            let thickness = match style {
+                PieceStyle::Lines => thickness,
+                PieceStyle::Solid => thickness * 2.0,
+            };
+            let first = (progress * 2.0).min(1.0);
+            let second = (progress * 2.0 - 1.0).max(0.0);
+            draw_line(
+                center_x - radius,
+                center_y - radius,
+                center_x - radius + 2.0 * radius * first,
+                center_y - radius + 2.0 * radius * first,
+                thickness,
+                color,
+            );
+            if second > 0.0 {
+                draw_line(
+                    center_x + radius,
+                    center_y - radius,
+                    center_x + radius - 2.0 * radius * second,
+                    center_y - radius + 2.0 * radius * second,
+                    thickness,
+                    color,
+                );
+            }
         }
-        WinType::Diagonal => {
-            draw_line(start_x, start_y, start_x + width, start_y + width, WIN_LINE_THICKNESS, color);
+        (Player::O, PieceStyle::Solid) => {
+            draw_circle(center_x, center_y, (radius + thickness / 2.0) * progress, color);
         }
-        WinType::AntiDiagonal => {
-            draw_line(
-                start_x + width,
-                start_y,
-                start_x,
-                start_y + width,
-                WIN_LINE_THICKNESS,
+        (Player::O, PieceStyle::Lines) => {
+            draw_arc(
+                center_x,
+                center_y,
+                30,
+                radius,
+                -90.0,
+                thickness,
+                360.0 * progress,
                 color,
             );
         }
     }
 }
 
-fn draw_x_o(board_start_x: f32, board_start_y: f32, game: &Game, hovered_cell: &mut Option<(usize, usize)>) {
-    *hovered_cell = None;
-    for row in 0..BOARD_SIZE {
-        for col in 0..BOARD_SIZE {
-            let x = board_start_x + col as f32 * CELL_SIZE;
-            let y = board_start_y + row as f32 * CELL_SIZE;
-            
-            let (mouse_x, mouse_y) = mouse_position();
-            let hover = mouse_x >= x && mouse_x <= x + CELL_SIZE &&
-                        mouse_y >= y && mouse_y <= y + CELL_SIZE;
-            
-            if hover && matches!(game.board[row][col], CellState::Empty) {
-                *hovered_cell = Some((row, col));
-                draw_rectangle(x, y, CELL_SIZE, CELL_SIZE, Color::from_rgba(40, 40, 40, 255));
-            }
-            
+/// Draws the pieces of `game` and a `preview_mark` on the `selected` cell.
+/// Only a `legal` board shows the preview; a `highlighted` board gets a
+/// tinted background, which ultimate mode uses to mark where the current
+/// player may move. Pieces are drawn as far in as `animation` says, the
+/// loser's pieces fade once the game is won, and cells the rules have
+/// closed are dimmed.
+#[allow(clippy::too_many_arguments)]
+fn draw_x_o(
+    theme: &Theme,
+    board_start_x: f32,
+    board_start_y: f32,
+    cell_size: f32,
+    game: &Game,
+    animation: &BoardAnimation,
+    preview_mark: Player,
+    legal: bool,
+    highlighted: bool,
+    selected: Option<(usize, usize)>,
+) {
+    let line_thickness = theme.line_thickness * cell_size / CELL_SIZE;
+
+    if highlighted {
+        let width = cell_size * game.size() as f32;
+        draw_rectangle(board_start_x, board_start_y, width, width, theme.highlight);
+    }
+
+    for row in 0..game.size() {
+        for col in 0..game.size() {
+            let x = board_start_x + col as f32 * cell_size;
+            let y = board_start_y + row as f32 * cell_size;
+            let hover = legal && selected == Some((row, col)) && game.is_open(row, col);
+
+            if hover {
+                draw_rectangle(x, y, cell_size, cell_size, theme.hover);
+            }
+
+            let center_x = x + cell_size / 2.0;
+            let center_y = y + cell_size / 2.0;
             match game.board[row][col] {
-                CellState::Occupied(Player::X) => {
-                    let center_x = x + CELL_SIZE / 2.0;
-                    let center_y = y + CELL_SIZE / 2.0;
-                    let radius = CELL_SIZE / 3.0;
-                    draw_line(
-                        center_x - radius,
-                        center_y - radius,
-                        center_x + radius,
-                        center_y + radius,
-                        LINE_THICKNESS * 2.0,
-                        Color::from_rgba(52, 152, 219, 255),
-                    );
-                    draw_line(
-                        center_x + radius,
-                        center_y - radius,
-                        center_x - radius,
-                        center_y + radius,
-                        LINE_THICKNESS * 2.0,
-                        Color::from_rgba(52, 152, 219, 255),
-                    );
-                }
-                CellState::Occupied(Player::O) => {
-                    let center_x = x + CELL_SIZE / 2.0;
-                    let center_y = y + CELL_SIZE / 2.0;
-                    let radius = CELL_SIZE / 3.0;
-                    draw_circle_lines(
+                CellState::Occupied(player) => {
+                    let alpha = match game.game_state {
+                        GameState::Win(winner, _) if winner != player && game.rules.players_own_marks() => {
+                            animation.losing_alpha()
+                        }
+                        _ => 255,
+                    };
+                    draw_piece(
+                        theme,
+                        player,
                         center_x,
                         center_y,
-                        radius,
-                        LINE_THICKNESS * 2.0,
-                        Color::from_rgba(231, 76, 60, 255),
+                        cell_size / 3.0,
+                        line_thickness * 2.0,
+                        alpha,
+                        animation.piece(row, col),
                     );
                 }
                 CellState::Empty => {
                     if hover {
-                        let center_x = x + CELL_SIZE / 2.0;
-                        let center_y = y + CELL_SIZE / 2.0;
-                        let radius = CELL_SIZE / 4.0;
-                        
-                        match game.current_player {
-                            Player::X => {
-                                draw_line(
-                                    center_x - radius,
-                                    center_y - radius,
-                                    center_x + radius,
-                                    center_y + radius,
-                                    LINE_THICKNESS,
-                                    Color::from_rgba(52, 152, 219, 100),
-                                );
-                                draw_line(
-                                    center_x + radius,
-                                    center_y - radius,
-                                    center_x - radius,
-                                    center_y + radius,
-                                    LINE_THICKNESS,
-                                    Color::from_rgba(52, 152, 219, 100),
-                                );
-                            }
-                            Player::O => {
-                                draw_circle_lines(
-                                    center_x,
-                                    center_y,
-                                    radius,
-                                    LINE_THICKNESS,
-                                    Color::from_rgba(231, 76, 60, 100),
-                                );
-                            }
-                        }
+                        draw_piece(theme, preview_mark, center_x, center_y, cell_size / 4.0, line_thickness, 100, 1.0);
                     }
                 }
             }
+
+            if matches!(game.game_state, GameState::Playing) && !game.rules.is_open(game, row, col) {
+                draw_rectangle(x, y, cell_size, cell_size, theme.shade(150));
+            }
+        }
+    }
+}
+
+/// The cell of a `size`x`size` board under `point`, if any.
+fn cell_at(board_start_x: f32, board_start_y: f32, cell_size: f32, size: usize, (x, y): (f32, f32)) -> Option<(usize, usize)> {
+    let col = ((x - board_start_x) / cell_size).floor();
+    let row = ((y - board_start_y) / cell_size).floor();
+    let in_board = |i: f32| i >= 0.0 && i < size as f32;
+    (in_board(row) && in_board(col)).then_some((row as usize, col as usize))
+}
+
+fn draw_cursor(theme: &Theme, x: f32, y: f32, width: f32, height: f32) {
+    draw_rectangle_lines(x - 4.0, y - 4.0, width + 8.0, height + 8.0, 3.0, theme.cursor);
+}
+
+/// Tints each empty cell by how the game ends if the player to move puts
+/// `mark` there and both sides then play perfectly, and labels it.
+fn draw_hints(theme: &Theme, board_start_x: f32, board_start_y: f32, cell_size: f32, hints: &[Hint], mark: Player) {
+    let font_size = (cell_size * 0.2) as u16;
+    for hint in hints.iter().filter(|hint| hint.mark == mark) {
+        let (label, color) = match hint.outcome {
+            Outcome::Win => ("WIN", theme.hint_win),
+            Outcome::Draw => ("DRAW", theme.hint_draw),
+            Outcome::Loss => ("LOSS", theme.hint_loss),
+        };
+        let x = board_start_x + hint.col as f32 * cell_size;
+        let y = board_start_y + hint.row as f32 * cell_size;
+        draw_rectangle(x, y, cell_size, cell_size, Color { a: 0.3, ..color });
+
+        let dim = measure_text(label, None, font_size, 1.0);
+        draw_text(
+            label,
+            x + (cell_size - dim.width) / 2.0,
+            y + (cell_size + dim.offset_y) / 2.0,
+            font_size as f32,
+            color,
+        );
+    }
+}
+
+/// Splits the ultimate board into its small boards: returns the gap between
+/// small boards, the width of one small board and the size of one cell.
+fn ultimate_layout(board_width: f32) -> (f32, f32, f32) {
+    let gap = board_width * ULTIMATE_GAP;
+    let small_width = (board_width - gap * (ULTIMATE_SIZE - 1) as f32) / ULTIMATE_SIZE as f32;
+    (gap, small_width, small_width / ULTIMATE_SIZE as f32)
+}
+
+/// Screen position of a cell of the ultimate board, addressed by its row and
+/// column on the whole 9x9 grid.
+fn ultimate_cell_position(board_start_x: f32, board_start_y: f32, board_width: f32, row: usize, col: usize) -> (f32, f32) {
+    let (gap, small_width, cell_size) = ultimate_layout(board_width);
+    let offset = |i: usize| (i / ULTIMATE_SIZE) as f32 * (small_width + gap) + (i % ULTIMATE_SIZE) as f32 * cell_size;
+    (board_start_x + offset(col), board_start_y + offset(row))
+}
+
+/// The ultimate-board cell under `point` as a row and column on the whole
+/// 9x9 grid, if any.
+fn ultimate_cell_at(board_start_x: f32, board_start_y: f32, board_width: f32, (x, y): (f32, f32)) -> Option<(usize, usize)> {
+    let (gap, small_width, cell_size) = ultimate_layout(board_width);
+    let index = |offset: f32| {
+        if offset < 0.0 {
+            return None;
+        }
+        let board = (offset / (small_width + gap)).floor() as usize;
+        let within = offset - board as f32 * (small_width + gap);
+        let cell = ((within / cell_size) as usize).min(ULTIMATE_SIZE - 1);
+        (board < ULTIMATE_SIZE && within < small_width).then_some(board * ULTIMATE_SIZE + cell)
+    };
+    Some((index(y - board_start_y)?, index(x - board_start_x)?))
+}
+
+/// Draws the nine small boards of an ultimate game, marks the boards that
+/// are open to the current player and covers won boards with a large piece.
+/// `selected` is a cell on the whole 9x9 grid.
+fn draw_ultimate(
+    theme: &Theme,
+    board_start_x: f32,
+    board_start_y: f32,
+    board_width: f32,
+    ultimate: &UltimateGame,
+    animation: &UltimateAnimation,
+    selected: Option<(usize, usize)>,
+) {
+    let (gap, small_width, cell_size) = ultimate_layout(board_width);
+
+    for i in 1..ULTIMATE_SIZE {
+        let pos = i as f32 * (small_width + gap) - gap / 2.0;
+        draw_line(
+            board_start_x + pos,
+            board_start_y,
+            board_start_x + pos,
+            board_start_y + board_width,
+            theme.line_thickness,
+            theme.grid,
+        );
+        draw_line(
+            board_start_x,
+            board_start_y + pos,
+            board_start_x + board_width,
+            board_start_y + pos,
+            theme.line_thickness,
+            theme.grid,
+        );
+    }
+
+    for board_row in 0..ULTIMATE_SIZE {
+        for board_col in 0..ULTIMATE_SIZE {
+            let x = board_start_x + board_col as f32 * (small_width + gap);
+            let y = board_start_y + board_row as f32 * (small_width + gap);
+            let board = &ultimate.boards[board_row][board_col];
+            let legal = ultimate.is_legal_board(board_row, board_col);
+            let selected_here = selected
+                .filter(|&(row, col)| (row / ULTIMATE_SIZE, col / ULTIMATE_SIZE) == (board_row, board_col))
+                .map(|(row, col)| (row % ULTIMATE_SIZE, col % ULTIMATE_SIZE));
+
+            draw_x_o(
+                theme,
+                x,
+                y,
+                cell_size,
+                board,
+                &animation.boards[board_row][board_col],
+                ultimate.current_player,
+                legal,
+                legal,
+                selected_here,
+            );
+
+            let inner_lines = theme.line_thickness * cell_size / CELL_SIZE;
+            for i in 1..ULTIMATE_SIZE {
+                let pos = i as f32 * cell_size;
+                draw_line(x + pos, y, x + pos, y + small_width, inner_lines, theme.inner_grid);
+                draw_line(x, y + pos, x + small_width, y + pos, inner_lines, theme.inner_grid);
+            }
+
+            if let GameState::Win(player, _) = board.game_state {
+                let alpha = match ultimate.game_state() {
+                    GameState::Win(winner, _) if *winner != player => animation.meta.losing_alpha(),
+                    _ => 255,
+                };
+                draw_rectangle(x, y, small_width, small_width, theme.shade(160));
+                draw_piece(
+                    theme,
+                    player,
+                    x + small_width / 2.0,
+                    y + small_width / 2.0,
+                    small_width / 3.0,
+                    theme.line_thickness * 2.0,
+                    alpha,
+                    animation.meta.piece(board_row, board_col),
+                );
+            }
         }
     }
+
+    if let GameState::Win(player, ref win_type) = *ultimate.game_state() {
+        draw_win_line(
+            theme,
+            board_start_x - gap / 2.0,
+            board_start_y - gap / 2.0,
+            small_width + gap,
+            win_type,
+            player,
+            animation.meta.win_line(),
+        );
+    }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut game = Game::new();
-    let mut hovered_cell: Option<(usize, usize)> = None;
-    let mut button_hovered = false;
+fn main() {
+    if std::env::args().any(|arg| arg == "--cli") {
+        cli_main();
+    } else if std::env::args().any(|arg| arg == "--tournament") {
+        tournament_main();
+    } else {
+        macroquad::Window::from_config(window_conf(), gui_main());
+    }
+}
+
+fn cli_main() {
+    let rules = rules_from_args();
+    let (size, win_length) = board_config_from_args(rules);
+    let mut game = Game::with_rules(size, win_length, rules);
+    if let Some(path) = arg_value("--load") {
+        match load_game(&path) {
+            Ok(loaded) => game = loaded,
+            Err(e) => {
+                eprintln!("Game load failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = cli::run(game, opponent_from_args(), io::stdin().lock(), &mut io::stdout()) {
+        eprintln!("Terminal play failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn tournament_main() {
+    let rules = rules_from_args();
+    let (size, win_length) = board_config_from_args(rules);
+    let start = Game::with_rules(size, win_length, rules);
+
+    let names = arg_value("--strategies").unwrap_or_else(|| "random,first-empty,greedy,perfect".to_string());
+    let mut strategies = Vec::new();
+    for name in names.split(',').map(str::trim) {
+        match ai::strategy_by_name(name) {
+            Some(strategy) => strategies.push(strategy),
+            None => {
+                eprintln!("Unknown strategy '{}'", name);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let games = match arg_value("--games").map(|games| games.parse::<u32>()) {
+        None => 100,
+        Some(Ok(games)) => games,
+        Some(Err(e)) => {
+            eprintln!("Invalid --games: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(seed) = arg_value("--seed") {
+        match seed.parse::<u64>() {
+            Ok(seed) => rand::srand(seed),
+            Err(e) => {
+                eprintln!("Invalid --seed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let pairings = tournament::run(&mut strategies, &start, games);
+    let written = if std::env::args().any(|arg| arg == "--matrix") {
+        tournament::write_matrix(&pairings, &mut io::stdout())
+    } else {
+        tournament::write_csv(&pairings, &mut io::stdout())
+    };
+    if let Err(e) = written {
+        eprintln!("Writing results failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn gui_main() {
+    let rules = rules_from_args();
+    let (size, win_length) = board_config_from_args(rules);
+    let mut game = Game::with_rules(size, win_length, rules);
+    let mut animation = BoardAnimation::new();
+    let mut message: Option<(String, f32)> = None;
+    let mut scoreboard = Scoreboard::load(data_path(STATS_FILE));
+
+    let mut net: Option<NetSession> = None;
+    if let Some(port) = arg_value("--host") {
+        match port.parse::<u16>() {
+            Ok(port) => match NetSession::host(port) {
+                Ok(session) => net = Some(session),
+                Err(e) => eprintln!("Could not host on port {}: {}", port, e),
+            },
+            Err(_) => eprintln!("Invalid port '{}'", port),
+        }
+    } else if let Some(address) = arg_value("--join") {
+        net = Some(NetSession::join(address));
+    }
+
+    // The peers only agree on the board, and moves don't say which mark was
+    // put down, so networked games follow the standard rules.
+    if net.is_some() && arg_value("--rules").is_some() {
+        eprintln!("--rules cannot be used with --host or --join, playing standard tic-tac-toe");
+        game = Game::new(size, win_length);
+    }
+
+    // Clocks would run apart on two machines, so networked games are untimed.
+    let mut clock = Clock::new(match net {
+        None => time_control_from_args(),
+        Some(_) => TimeControl::default(),
+    });
+
+    if let Some(path) = arg_value("--load") {
+        match load_game(&path) {
+            Ok(loaded) => {
+                game = loaded;
+                message = Some((format!("LOADED {}", path), MESSAGE_DURATION));
+                scoreboard.start_round(&game.game_state);
+                clock.reset();
+            }
+            Err(e) => {
+                eprintln!("Game load failed: {}", e);
+                message = Some(("COULD NOT LOAD GAME, SEE CONSOLE".to_string(), MESSAGE_DURATION));
+            }
+        }
+    }
+    let mut input_mode = InputMode::Mouse;
+    let mut last_mouse = mouse_position();
+    let mut focus = Focus::Cell(1, 1);
+    let mut picked_mark = Player::X;
+    // Both players are people in a networked game.
+    let mut opponent = if net.is_some() {
+        if arg_value("--ai").is_some() {
+            eprintln!("--ai cannot be used with --host or --join, playing against the remote player");
+        }
+        None
+    } else {
+        opponent_from_args()
+    };
+    let mut ai_timer = 0.0;
+    let mut replay_step: Option<usize> = None;
+    let mut replay_timer = 0.0;
+    let mut ultimate: Option<UltimateGame> = None;
+    let mut ultimate_animation = UltimateAnimation::new();
+    // A theme loaded from a file joins the end of the built-in ones.
+    let mut themes = Theme::built_in();
+    let loaded_theme = theme_from_args();
+    let mut show_hints = false;
+    // The game the hints were worked out for, in notation, and the hints,
+    // which are `None` when it has too many moves left to solve.
+    let mut hint_cache: Option<(String, Option<Vec<Hint>>)> = None;
+    let mut theme_index = match themes.iter().position(|theme| *theme == loaded_theme) {
+        Some(i) => i,
+        None => {
+            themes.push(loaded_theme);
+            themes.len() - 1
+        }
+    };
 
     loop {
-        clear_background(Color::from_rgba(0, 0, 0, 255));
+        let theme = &themes[theme_index];
+        clear_background(theme.background);
+
+        if is_key_pressed(KeyCode::U) && net.is_none() {
+            ultimate = match ultimate {
+                Some(_) => {
+                    game.restart_with(game.first_player);
+                    scoreboard.start_round(&game.game_state);
+                    clock.reset();
+                    None
+                }
+                None => {
+                    let ultimate = UltimateGame::new();
+                    scoreboard.start_round(ultimate.game_state());
+                    Some(ultimate)
+                }
+            };
+            replay_step = None;
+        }
 
-        let board_width = CELL_SIZE * BOARD_SIZE as f32;
+        let available_width = (screen_width() - 2.0 * BOARD_PADDING).min(screen_height() - 350.0);
+        let cell_size = (available_width / game.size() as f32).min(CELL_SIZE);
+        let board_width = match ultimate {
+            Some(_) => available_width.min(CELL_SIZE * ULTIMATE_SIZE as f32),
+            None => cell_size * game.size() as f32,
+        };
         let board_start_x = (screen_width() - board_width) / 2.0;
         let board_start_y = (screen_height() - board_width) / 2.0 - 30.0;
 
-        draw_centered_text("TIC-TAC-TOE", 60, 70.0, Color::from_rgba(200, 200, 200, 255));
+        let replay_game = replay_step.map(|step| game.position_after(step));
+        let view = replay_game.as_ref().unwrap_or(&game);
+
+        let mouse = mouse_position();
+        let clicked = is_mouse_button_pressed(MouseButton::Left);
+        if mouse != last_mouse || clicked {
+            input_mode = InputMode::Mouse;
+            last_mouse = mouse;
+        }
+
+        let ctrl_down = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift_down = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let grid_size = match ultimate {
+            Some(_) => ULTIMATE_SIZE * ULTIMATE_SIZE,
+            None => game.size(),
+        };
+        focus = focus.clamped(grid_size);
+        if replay_step.is_none()
+            && !ctrl_down
+            && let Some(step) = input::cursor_step()
+        {
+            focus = focus.step(step, grid_size);
+            input_mode = InputMode::Keyboard;
+        }
+        let numpad_cell = input::numpad_cell();
+        if numpad_cell.is_some() {
+            input_mode = InputMode::Keyboard;
+        }
+        let confirm = input::confirm_pressed();
+        if confirm {
+            input_mode = InputMode::Keyboard;
+        }
+        let keyboard_cell = match (input_mode, focus) {
+            (InputMode::Keyboard, Focus::Cell(row, col)) => Some((row, col)),
+            _ => None,
+        };
+        let play_again_focused = input_mode == InputMode::Keyboard && focus == Focus::PlayAgain;
+
+        draw_centered_text("TIC-TAC-TOE", 60, 70.0, theme.title);
+        draw_scoreboard(theme, &scoreboard, 105.0);
+
+        let opponent_text = match (&net, opponent) {
+            (Some(session), _) => {
+                let state = match &session.status {
+                    NetStatus::Waiting => match session.port() {
+                        Some(port) => format!("WAITING ON PORT {}", port),
+                        None => "CONNECTING".to_string(),
+                    },
+                    NetStatus::Connected => "CONNECTED".to_string(),
+                    NetStatus::Error(_) => "DISCONNECTED".to_string(),
+                };
+                format!("ONLINE AS {}: {}", player_name(session.local_player), state)
+            }
+            // The AI only plays the single board.
+            (None, _) if ultimate.is_some() => "OPPONENT: HUMAN".to_string(),
+            (None, None) => "[M] OPPONENT: HUMAN".to_string(),
+            (None, Some(difficulty)) => format!("[M] OPPONENT: AI ({})", difficulty.name()),
+        };
+        draw_text(&opponent_text, 10.0, 20.0, 20.0, theme.info);
+        let mut theme_text = format!("[T] THEME: {}", theme.name.to_uppercase());
+        if net.is_none() && ultimate.is_none() {
+            theme_text += if show_hints { "  [H] HINTS: ON" } else { "  [H] HINTS: OFF" };
+        }
+        draw_text(&theme_text, 10.0, 40.0, 20.0, theme.info);
+        if net.is_none() && ultimate.is_none() {
+            let clock_text = format!("[C] CLOCK: {}", time_control_name(clock.control));
+            draw_text(&clock_text, 10.0, 60.0, 20.0, theme.info);
+        }
+
+        let board_text = match ultimate {
+            Some(_) => "[U] MODE: ULTIMATE".to_string(),
+            None => format!(
+                "[B] BOARD: {}x{}, {} IN A ROW  [U] ULTIMATE",
+                game.size(),
+                game.size(),
+                game.win_length
+            ),
+        };
+        let board_text_width = measure_text(&board_text, None, 20, 1.0).width;
+        draw_text(
+            &board_text,
+            screen_width() - board_text_width - 10.0,
+            20.0,
+            20.0,
+            theme.info,
+        );
+
+        if ultimate.is_none() {
+            let mut rules_text = format!("[R] RULES: {}", game.rules.name().to_uppercase());
+            if game.marks().len() > 1 {
+                rules_text += &format!("  [TAB] MARK: {}", player_name(mark_to_play(&game, picked_mark)));
+            }
+            let rules_text_width = measure_text(&rules_text, None, 20, 1.0).width;
+            draw_text(
+                &rules_text,
+                screen_width() - rules_text_width - 10.0,
+                40.0,
+                20.0,
+                theme.info,
+            );
+        }
 
         draw_rectangle(
             board_start_x - 20.0,
             board_start_y - 20.0,
             board_width + 40.0,
             board_width + 40.0,
-            Color::from_rgba(20, 20, 20, 255),
+            theme.board,
         );
         draw_rectangle_lines(
             board_start_x - 20.0,
@@ -304,98 +939,484 @@ async fn main() {
             board_width + 40.0,
             board_width + 40.0,
             2.0,
-            Color::from_rgba(100, 100, 100, 255),
+            theme.board_border,
         );
 
-        draw_grid_lines(board_start_x, board_start_y, CELL_SIZE, LINE_THICKNESS);
+        if let Some(ultimate) = &mut ultimate {
+            let selected = match input_mode {
+                InputMode::Mouse => ultimate_cell_at(board_start_x, board_start_y, board_width, mouse),
+                InputMode::Keyboard => keyboard_cell,
+            };
+            ultimate_animation.update(ultimate, get_frame_time());
+            draw_ultimate(theme, board_start_x, board_start_y, board_width, ultimate, &ultimate_animation, selected);
+            if let Some((row, col)) = keyboard_cell {
+                let (x, y) = ultimate_cell_position(board_start_x, board_start_y, board_width, row, col);
+                let (_, _, cell_size) = ultimate_layout(board_width);
+                draw_cursor(theme, x, y, cell_size, cell_size);
+            }
+
+            let (status, status_color) = status_text(theme, ultimate.game_state(), ultimate.current_player);
+            draw_centered_text(&status, 40, board_start_y + board_width + 70.0, status_color);
+
+            let button_width = 220.0;
+            let button_height = 60.0;
+            let button_x = screen_width() / 2.0 - button_width / 2.0;
+            let button_y = board_start_y + board_width + 120.0;
+            let button_hovered = draw_button(
+            theme,
+                "PLAY AGAIN",
+                button_x,
+                button_y,
+                button_width,
+                button_height,
+                36,
+                true,
+            );
+            if play_again_focused {
+                draw_cursor(theme, button_x, button_y, button_width, button_height);
+            }
+
+            // On the 3x3 number pad, pick a cell of the board the player was sent to.
+            let numpad_move = numpad_cell.and_then(|(row, col)| {
+                let (board_row, board_col) = ultimate.next_board?;
+                Some((board_row * ULTIMATE_SIZE + row, board_col * ULTIMATE_SIZE + col))
+            });
+            let target = if clicked {
+                selected
+            } else if confirm {
+                keyboard_cell
+            } else {
+                numpad_move
+            };
+            if let Some((row, col)) = target {
+                ultimate.make_move(row / ULTIMATE_SIZE, col / ULTIMATE_SIZE, row % ULTIMATE_SIZE, col % ULTIMATE_SIZE);
+                if numpad_move.is_some() {
+                    focus = Focus::Cell(row, col);
+                }
+                if !matches!(ultimate.game_state(), GameState::Playing) && input_mode == InputMode::Keyboard {
+                    focus = Focus::PlayAgain;
+                }
+            }
+            if (clicked && button_hovered) || (confirm && play_again_focused) {
+                ultimate.reset();
+                scoreboard.start_round(ultimate.game_state());
+                focus = Focus::Cell(ULTIMATE_SIZE * ULTIMATE_SIZE / 2, ULTIMATE_SIZE * ULTIMATE_SIZE / 2);
+            }
+            scoreboard.update(ultimate.game_state());
+
+            next_frame().await;
+            continue;
+        }
+
+        animation.update(&view.board, &view.game_state, get_frame_time());
 
-        if let GameState::Win(player, ref win_type) = game.game_state {
+        draw_grid_lines(
+            board_start_x,
+            board_start_y,
+            cell_size,
+            view.size(),
+            theme.line_thickness * cell_size / CELL_SIZE,
+            theme.grid,
+        );
+        if let Some(side) = view.rules.sub_board_size(view) {
+            draw_grid_lines(
+                board_start_x,
+                board_start_y,
+                cell_size * side as f32,
+                view.size() / side,
+                3.0 * theme.line_thickness * cell_size / CELL_SIZE,
+                theme.grid,
+            );
+        }
+
+        // The line is drawn in the color of its pieces, which under misère
+        // rules are the loser's.
+        if let GameState::Win(_, ref win_type) = view.game_state
+            && let CellState::Occupied(mark) = view.board[win_type.start.0][win_type.start.1]
+        {
             draw_win_line(
+                theme,
                 board_start_x,
                 board_start_y,
-                board_width,
+                cell_size,
                 win_type,
-                match player {
-                    Player::X => Color::from_rgba(52, 152, 219, 255),
-                    Player::O => Color::from_rgba(231, 76, 60, 255),
-                },
+                mark,
+                animation.win_line(),
             );
         }
 
-        draw_x_o(board_start_x, board_start_y, &game, &mut hovered_cell);
-
-        let status = match game.game_state {
-            GameState::Playing => format!("Player {}'s turn", 
-                match game.current_player {
-                    Player::X => "X",
-                    Player::O => "O",
-                }),
-            GameState::Win(player, _) => format!("Player {} wins!", 
-                match player {
-                    Player::X => "X",
-                    Player::O => "O",
-                }),
-            GameState::Draw => "It's a draw!".to_string(),
+        let selected = match input_mode {
+            InputMode::Mouse => cell_at(board_start_x, board_start_y, cell_size, view.size(), mouse),
+            InputMode::Keyboard => keyboard_cell,
         };
+        draw_x_o(
+            theme,
+            board_start_x,
+            board_start_y,
+            cell_size,
+            view,
+            &animation,
+            mark_to_play(view, picked_mark),
+            true,
+            false,
+            selected,
+        );
+        if show_hints && net.is_none() && matches!(view.game_state, GameState::Playing) {
+            let notation = view.to_notation();
+            if hint_cache.as_ref().is_none_or(|(solved, _)| *solved != notation) {
+                hint_cache = Some((notation, solver::hints(view)));
+            }
+            match hint_cache.as_ref().and_then(|(_, hints)| hints.as_ref()) {
+                Some(hints) => {
+                    draw_hints(theme, board_start_x, board_start_y, cell_size, hints, mark_to_play(view, picked_mark));
+                }
+                None => draw_centered_text(
+                    &format!("HINTS NEED {} OR FEWER MOVES LEFT", solver::SOLVE_LIMIT),
+                    20,
+                    board_start_y - 30.0,
+                    theme.info,
+                ),
+            }
+        }
+        if let Some((row, col)) = keyboard_cell {
+            draw_cursor(
+                theme,
+                board_start_x + col as f32 * cell_size,
+                board_start_y + row as f32 * cell_size,
+                cell_size,
+                cell_size,
+            );
+        }
 
-        let status_color = match game.game_state {
-            GameState::Win(Player::X, _) => Color::from_rgba(52, 152, 219, 255),
-            GameState::Win(Player::O, _) => Color::from_rgba(231, 76, 60, 255),
-            _ => Color::from_rgba(100, 100, 100, 255),
-        };
-        
+        let (status, status_color) = status_text(theme, &view.game_state, view.current_player);
         draw_centered_text(&status, 40, board_start_y + board_width + 70.0, status_color);
+        if clock.control.is_timed() && replay_step.is_none() {
+            draw_clocks(
+                theme,
+                &clock,
+                &game,
+                board_start_x - 20.0,
+                board_start_x + board_width + 20.0,
+                board_start_y + board_width + 70.0,
+            );
+        }
+
+        let info_y = screen_height() - 10.0;
+        if let Some(step) = replay_step {
+            draw_centered_text(
+                &format!("REPLAY: MOVE {}/{} (LEFT/RIGHT TO STEP)", step, game.history.len()),
+                20,
+                info_y,
+                theme.info,
+            );
+        } else if let Some(NetSession { status: NetStatus::Error(reason), .. }) = &net {
+            draw_centered_text(
+                &format!("NETWORK ERROR: {}", reason.to_uppercase()),
+                20,
+                info_y,
+                theme.error,
+            );
+        } else if let Some((text, remaining)) = &mut message {
+            draw_centered_text(text, 20, info_y, theme.info);
+            *remaining -= get_frame_time();
+            if *remaining <= 0.0 {
+                message = None;
+            }
+        }
 
-        let button_text = "PLAY AGAIN";
-        let button_width = 220.0;
         let button_height = 60.0;
-        let button_x = screen_width() / 2.0 - button_width / 2.0;
+        let button_gap = 12.0;
+        let small_button_width = 110.0;
+        let play_again_width = 220.0;
+        let replay_width = 130.0;
+        let row_width = 2.0 * small_button_width + play_again_width + replay_width + 3.0 * button_gap;
+        let undo_x = screen_width() / 2.0 - row_width / 2.0;
+        let redo_x = undo_x + small_button_width + button_gap;
+        let play_again_x = redo_x + small_button_width + button_gap;
+        let replay_x = play_again_x + play_again_width + button_gap;
         let button_y = board_start_y + board_width + 120.0;
-        
-        let (mouse_x, mouse_y) = mouse_position();
-        button_hovered = mouse_x >= button_x && mouse_x <= button_x + button_width &&
-                         mouse_y >= button_y && mouse_y <= button_y + button_height;
-        
-        let button_color = if button_hovered {
-            Color::from_rgba(46, 204, 113, 255)
-        } else {
-            Color::from_rgba(39, 174, 96, 255)
-        };
-        
-        draw_rectangle(
-            button_x,
+
+        let game_finished = !matches!(game.game_state, GameState::Playing);
+        let undo_hovered = draw_button(
+            theme,
+            "UNDO",
+            undo_x,
             button_y,
-            button_width,
+            small_button_width,
             button_height,
-            button_color,
+            28,
+            replay_step.is_none() && net.is_none() && !game.timed_out() && !game.history.is_empty(),
         );
-        
-        draw_rectangle_lines(
-            button_x,
+        let redo_hovered = draw_button(
+            theme,
+            "REDO",
+            redo_x,
             button_y,
-            button_width,
+            small_button_width,
             button_height,
-            3.0,
-            Color::from_rgba(30, 130, 76, 255),
+            28,
+            replay_step.is_none() && net.is_none() && !game.timed_out() && !game.redo_history.is_empty(),
         );
-        
-        draw_centered_text(
-            button_text,
+        let button_hovered = draw_button(
+            theme,
+            "PLAY AGAIN",
+            play_again_x,
+            button_y,
+            play_again_width,
+            button_height,
             36,
-            button_y + button_height / 2.0 + 12.0,
-            WHITE,
+            true,
         );
+        if play_again_focused {
+            draw_cursor(theme, play_again_x, button_y, play_again_width, button_height);
+        }
+        let replay_hovered = draw_button(
+            theme,
+            if replay_step.is_some() { "STOP" } else { "REPLAY" },
+            replay_x,
+            button_y,
+            replay_width,
+            button_height,
+            28,
+            game_finished,
+        );
+
+        if let Some(session) = &mut net {
+            let was_connected = session.is_connected();
+            for message in session.poll() {
+                match message {
+                    Message::Hello { size, win_length }
+                        if session.local_player == Player::O
+                            && (DEFAULT_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size)
+                            && (DEFAULT_WIN_LENGTH..=size).contains(&win_length) =>
+                    {
+                        game = Game::new(size, win_length);
+                        scoreboard.start_round(&game.game_state);
+                        clock.reset();
+                    }
+                    Message::Hello { .. } => session.fail("unexpected greeting".to_string()),
+                    Message::Move { row, col, hash } => {
+                        let legal = game.current_player != session.local_player
+                            && row < game.size()
+                            && col < game.size()
+                            && game.make_move(row, col);
+                        if !legal {
+                            session.fail("desync: opponent move is illegal here".to_string());
+                        } else if net::board_hash(&game) != hash {
+                            session.fail("desync: boards differ".to_string());
+                        }
+                    }
+                    Message::Reset => {
+                        game.reset();
+                        scoreboard.start_round(&game.game_state);
+                        clock.reset();
+                    }
+                }
+            }
+
+            if !was_connected && session.is_connected() && session.local_player == Player::X {
+                game = Game::new(game.size(), game.win_length);
+                scoreboard.start_round(&game.game_state);
+                clock.reset();
+                session.send(&Message::Hello {
+                    size: game.size(),
+                    win_length: game.win_length,
+                });
+            }
+        }
+
+        let ai_turn = opponent.is_some() && game.current_player == Player::O;
+        let remote_turn = net
+            .as_ref()
+            .is_some_and(|session| !session.is_connected() || session.local_player != game.current_player);
+
+        if net.is_none() && is_key_pressed(KeyCode::M) {
+            opponent = match opponent {
+                None => Some(Difficulty::Random),
+                Some(Difficulty::Random) => Some(Difficulty::Greedy),
+                Some(Difficulty::Greedy) => Some(Difficulty::Perfect),
+                Some(Difficulty::Perfect) => None,
+            };
+            game.restart_with(game.first_player);
+            scoreboard.start_round(&game.game_state);
+            clock.reset();
+            ai_timer = 0.0;
+            replay_step = None;
+        }
+
+        if ctrl_down && is_key_pressed(KeyCode::S) {
+            let path = data_path(SAVE_FILE);
+            message = match save_game(&game, &path) {
+                Ok(()) => {
+                    println!("Saved to {}: {}", path.display(), game.to_notation());
+                    Some(("GAME SAVED, SEE CONSOLE".to_string(), MESSAGE_DURATION))
+                }
+                Err(e) => {
+                    eprintln!("Game save failed: {}", e);
+                    Some(("COULD NOT SAVE GAME, SEE CONSOLE".to_string(), MESSAGE_DURATION))
+                }
+            };
+        }
+
+        if net.is_none() && is_key_pressed(KeyCode::B) {
+            let presets = game.rules.presets();
+            let current = presets
+                .iter()
+                .position(|&preset| preset == (game.size(), game.win_length));
+            let (size, win_length) = match current {
+                Some(i) => presets[(i + 1) % presets.len()],
+                None => presets[0],
+            };
+            let first_player = game.first_player;
+            game = Game::with_rules(size, win_length, game.rules);
+            game.restart_with(first_player);
+            scoreboard.start_round(&game.game_state);
+            clock.reset();
+            ai_timer = 0.0;
+            replay_step = None;
+        }
+
+        if net.is_none() && ultimate.is_none() && is_key_pressed(KeyCode::C) {
+            let current = TIME_CONTROLS.iter().position(|&control| control == clock.control);
+            clock = Clock::new(TIME_CONTROLS[current.map_or(0, |i| (i + 1) % TIME_CONTROLS.len())]);
+            game.restart_with(game.first_player);
+            scoreboard.start_round(&game.game_state);
+            ai_timer = 0.0;
+            replay_step = None;
+        }
+
+        if net.is_none() && ultimate.is_none() && is_key_pressed(KeyCode::R) {
+            let rules = next_rules(game.rules);
+            let board = (game.size(), game.win_length);
+            let (size, win_length) = if rules.presets().contains(&board) {
+                board
+            } else {
+                rules.presets()[0]
+            };
+            let first_player = game.first_player;
+            game = Game::with_rules(size, win_length, rules);
+            game.restart_with(first_player);
+            scoreboard.start_round(&game.game_state);
+            clock.reset();
+            ai_timer = 0.0;
+            replay_step = None;
+        }
+
+        if net.is_none() && is_key_pressed(KeyCode::H) {
+            show_hints = !show_hints;
+        }
+
+        if is_key_pressed(KeyCode::T) {
+            theme_index = (theme_index + 1) % themes.len();
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            picked_mark = picked_mark.other();
+        }
+
+        if replay_step.is_none() && net.is_none() {
+            let undo_requested = (ctrl_down && !shift_down && is_key_pressed(KeyCode::Z))
+                || (clicked && undo_hovered);
+            let redo_requested = (ctrl_down && is_key_pressed(KeyCode::Y))
+                || (ctrl_down && shift_down && is_key_pressed(KeyCode::Z))
+                || (clicked && redo_hovered);
+
+            // Against the AI, step back and forth over whole rounds so it is
+            // always the human's turn afterwards.
+            if undo_requested && game.undo() {
+                if opponent.is_some() && game.current_player == Player::O {
+                    game.undo();
+                }
+                ai_timer = 0.0;
+            }
+            if redo_requested && game.redo() {
+                if opponent.is_some() && game.current_player == Player::O {
+                    game.redo();
+                }
+                ai_timer = 0.0;
+            }
+        }
+
+        if (clicked && replay_hovered) || (replay_step.is_some() && is_key_pressed(KeyCode::Escape)) {
+            replay_step = match replay_step {
+                Some(_) => None,
+                None => Some(0),
+            };
+            replay_timer = 0.0;
+        }
 
-        if is_mouse_button_pressed(MouseButton::Left) {
-            if let Some((row, col)) = hovered_cell {
-                game.make_move(row, col);
+        if let Some(step) = replay_step {
+            replay_timer += get_frame_time();
+            let mut next_step = step;
+            if is_key_pressed(KeyCode::Left) {
+                next_step = step.saturating_sub(1);
+                replay_timer = 0.0;
             }
-            
-            if button_hovered {
-                game.reset();
+            if is_key_pressed(KeyCode::Right) || replay_timer >= REPLAY_STEP_DELAY {
+                next_step = step + 1;
+                replay_timer = 0.0;
             }
+            replay_step = Some(next_step.min(game.history.len()));
         }
 
+        if let Some(difficulty) = opponent
+            && ai_turn
+            && replay_step.is_none()
+            && matches!(game.game_state, GameState::Playing)
+        {
+            ai_timer += get_frame_time();
+            if ai_timer >= AI_MOVE_DELAY {
+                ai_timer = 0.0;
+                if let Some((row, col, mark)) = ai::choose_move(&game, difficulty) {
+                    game.make_move_as(row, col, mark);
+                }
+            }
+        }
+
+        // The number pad only maps onto a 3x3 board.
+        let numpad_move = numpad_cell.filter(|_| game.size() == 3);
+        let target = if clicked {
+            selected
+        } else if confirm {
+            keyboard_cell
+        } else {
+            numpad_move
+        };
+        if let Some((row, col)) = target
+            && !ai_turn
+            && !remote_turn
+            && replay_step.is_none()
+            && game.make_move_as(row, col, mark_to_play(&game, picked_mark))
+        {
+            if numpad_move.is_some() {
+                focus = Focus::Cell(row, col);
+            }
+            if !matches!(game.game_state, GameState::Playing) && input_mode == InputMode::Keyboard {
+                focus = Focus::PlayAgain;
+            }
+            if let Some(session) = &mut net {
+                let hash = net::board_hash(&game);
+                session.send(&Message::Move { row, col, hash });
+            }
+        }
+
+        if (clicked && button_hovered) || (confirm && play_again_focused) {
+            if let Some(session) = &mut net {
+                session.send(&Message::Reset);
+            }
+            game.reset();
+            scoreboard.start_round(&game.game_state);
+            clock.reset();
+            ai_timer = 0.0;
+            replay_step = None;
+            focus = Focus::Cell(game.size() / 2, game.size() / 2);
+        }
+
+        if replay_step.is_none() {
+            clock.tick(&mut game, get_frame_time());
+        }
+        scoreboard.update(&game.game_state);
+
         next_frame().await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,270 @@
+//! Two-player games over TCP.
+//!
+//! One instance hosts (`--host <port>`) and plays X, the other joins
+//! (`--join <address>`) and plays O. Messages are single text lines:
+//!
+//! ```text
+//! HELLO <size> <win length>    host -> guest, once connected
+//! MOVE <row> <col> <hash>      after every move, hash of the board after it
+//! RESET                        either side pressed PLAY AGAIN
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use tictactoe::{CellState, Game, Player};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    Hello { size: usize, win_length: usize },
+    Move { row: usize, col: usize, hash: u64 },
+    Reset,
+}
+
+impl Message {
+    fn to_line(&self) -> String {
+        match self {
+            Message::Hello { size, win_length } => format!("HELLO {} {}\n", size, win_length),
+            Message::Move { row, col, hash } => format!("MOVE {} {} {}\n", row, col, hash),
+            Message::Reset => "RESET\n".to_string(),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Message> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["HELLO", size, win_length] => Some(Message::Hello {
+                size: size.parse().ok()?,
+                win_length: win_length.parse().ok()?,
+            }),
+            ["MOVE", row, col, hash] => Some(Message::Move {
+                row: row.parse().ok()?,
+                col: col.parse().ok()?,
+                hash: hash.parse().ok()?,
+            }),
+            ["RESET"] => Some(Message::Reset),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum NetStatus {
+    Waiting,
+    Connected,
+    Error(String),
+}
+
+enum NetEvent {
+    Connected(TcpStream),
+    Message(Message),
+    Failed(String),
+}
+
+pub struct NetSession {
+    pub local_player: Player,
+    pub status: NetStatus,
+    port: Option<u16>,
+    events: Receiver<NetEvent>,
+    writer: Option<TcpStream>,
+}
+
+impl NetSession {
+    /// Listens on `port` and plays X once a guest connects.
+    pub fn host(port: u16) -> io::Result<NetSession> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let port = listener.local_addr()?.port();
+        let (sender, events) = mpsc::channel();
+
+        thread::spawn(move || match listener.accept() {
+            Ok((stream, _)) => run_connection(stream, sender),
+            Err(e) => {
+                let _ = sender.send(NetEvent::Failed(e.to_string()));
+            }
+        });
+
+        Ok(NetSession {
+            local_player: Player::X,
+            status: NetStatus::Waiting,
+            port: Some(port),
+            events,
+            writer: None,
+        })
+    }
+
+    /// Connects to a host at `address` and plays O.
+    pub fn join(address: String) -> NetSession {
+        let (sender, events) = mpsc::channel();
+
+        thread::spawn(move || match TcpStream::connect(&address) {
+            Ok(stream) => run_connection(stream, sender),
+            Err(e) => {
+                let _ = sender.send(NetEvent::Failed(format!("{}: {}", address, e)));
+            }
+        });
+
+        NetSession {
+            local_player: Player::O,
+            status: NetStatus::Waiting,
+            port: None,
+            events,
+            writer: None,
+        }
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.status == NetStatus::Connected
+    }
+
+    /// Returns the messages received since the last call, without blocking.
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                NetEvent::Connected(stream) => {
+                    self.writer = Some(stream);
+                    self.status = NetStatus::Connected;
+                }
+                NetEvent::Message(message) => messages.push(message),
+                NetEvent::Failed(e) => self.fail(e),
+            }
+        }
+        messages
+    }
+
+    pub fn send(&mut self, message: &Message) {
+        if let Some(stream) = &mut self.writer
+            && let Err(e) = stream.write_all(message.to_line().as_bytes())
+        {
+            self.fail(format!("send failed: {}", e));
+        }
+    }
+
+    /// Puts the session into the error state. Once failed it stays failed,
+    /// keeping the first reason.
+    pub fn fail(&mut self, reason: String) {
+        if !matches!(self.status, NetStatus::Error(_)) {
+            self.status = NetStatus::Error(reason);
+        }
+        if let Some(stream) = self.writer.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for NetSession {
+    fn drop(&mut self) {
+        if let Some(stream) = &self.writer {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn run_connection(stream: TcpStream, sender: Sender<NetEvent>) {
+    let writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            let _ = sender.send(NetEvent::Failed(e.to_string()));
+            return;
+        }
+    };
+    if sender.send(NetEvent::Connected(writer)).is_err() {
+        return;
+    }
+
+    for line in BufReader::new(stream).lines() {
+        let event = match line {
+            Ok(line) => match Message::parse(&line) {
+                Some(message) => NetEvent::Message(message),
+                None => NetEvent::Failed(format!("invalid message '{}'", line)),
+            },
+            Err(e) => NetEvent::Failed(format!("connection lost: {}", e)),
+        };
+        if sender.send(event).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(NetEvent::Failed("opponent disconnected".to_string()));
+}
+
+/// FNV-1a hash of the board, sent with every move so both sides can tell
+/// when their games have drifted apart.
+pub fn board_hash(game: &Game) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for cell in game.board.iter().flatten() {
+        let byte = match cell {
+            CellState::Empty => 0,
+            CellState::Occupied(Player::X) => 1,
+            CellState::Occupied(Player::O) => 2,
+        };
+        hash ^= byte;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn poll_until(session: &mut NetSession, done: impl Fn(&NetSession, &[Message]) -> bool) -> Vec<Message> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = Vec::new();
+        while !done(session, &received) {
+            assert!(Instant::now() < deadline, "timed out waiting for network");
+            received.extend(session.poll());
+            thread::sleep(Duration::from_millis(10));
+        }
+        received
+    }
+
+    #[test]
+    fn messages_round_trip_through_text() {
+        for message in [
+            Message::Hello { size: 15, win_length: 5 },
+            Message::Move { row: 2, col: 1, hash: u64::MAX },
+            Message::Reset,
+        ] {
+            assert_eq!(Message::parse(&message.to_line()), Some(message));
+        }
+        assert_eq!(Message::parse("MOVE 1 x 3"), None);
+        assert_eq!(Message::parse("JUMP"), None);
+    }
+
+    #[test]
+    fn board_hash_tracks_moves() {
+        let mut a = Game::new(3, 3);
+        let mut b = Game::new(3, 3);
+        assert_eq!(board_hash(&a), board_hash(&b));
+
+        a.make_move(0, 0);
+        assert_ne!(board_hash(&a), board_hash(&b));
+
+        b.make_move(0, 0);
+        assert_eq!(board_hash(&a), board_hash(&b));
+    }
+
+    #[test]
+    fn host_and_guest_exchange_messages_over_localhost() {
+        let mut host = NetSession::host(0).unwrap();
+        let mut guest = NetSession::join(format!("127.0.0.1:{}", host.port().unwrap()));
+
+        poll_until(&mut host, |s, _| s.is_connected());
+        poll_until(&mut guest, |s, _| s.is_connected());
+
+        let hello = Message::Hello { size: 3, win_length: 3 };
+        host.send(&hello);
+        let received = poll_until(&mut guest, |_, received| !received.is_empty());
+        assert_eq!(received, vec![hello]);
+
+        drop(host);
+        poll_until(&mut guest, |s, _| matches!(s.status, NetStatus::Error(_)));
+    }
+}
@@ -0,0 +1,494 @@
+//! Text notation for saving and loading games.
+//!
+//! A game is written as `<size>/<win length>`, the board rows separated by
+//! `/` (`X`, `O` or `.` per cell) and the move list, all separated by
+//! whitespace:
+//!
+//! ```text
+//! 3/3 XXX/OO./... X:a1 O:a2 X:b1 O:b2 X:c1
+//! ```
+//!
+//! Cells are named by a column letter and a row number counted from the top
+//! left, so `a1` is the top-left cell and `c3` the bottom-right one on a 3x3
+//! board. The board is the position reached; pieces the move list does not
+//! account for were already on the board when play started, so a position
+//! on its own, or a position and the moves played from it, load as well as
+//! a whole game. Loading checks that the starting position could be reached,
+//! replays the move list through [`Game::make_move`] and checks that it ends
+//! on the given board.
+//!
+//! A game lost on time ends with `timeout:<player>`, naming the player whose
+//! time ran out.
+//!
+//! Games under other rules than the standard ones start with the rule set's
+//! name, as in `misere 3/3 ...`. The move list names the player who moved;
+//! the mark they put down is the one on the board.
+
+use crate::rules::{Rules, Standard, rules_by_name};
+use crate::{CellState, DEFAULT_WIN_LENGTH, Game, GameState, MAX_BOARD_SIZE, Player};
+
+impl Game {
+    pub fn to_notation(&self) -> String {
+        let rows: Vec<String> = self
+            .board
+            .iter()
+            .map(|row| row.iter().map(|&cell| cell_char(cell)).collect())
+            .collect();
+
+        let mut parts = Vec::new();
+        if self.rules.name() != Standard.name() {
+            parts.push(self.rules.name().to_string());
+        }
+        parts.push(format!("{}/{}", self.size(), self.win_length));
+        parts.push(rows.join("/"));
+        parts.extend(
+            self.history
+                .iter()
+                .map(|next| format!("{}:{}", player_char(next.player), cell_name(next.row, next.col))),
+        );
+        if let GameState::Timeout(player) = self.game_state {
+            parts.push(format!("timeout:{}", player_char(player)));
+        }
+        parts.join(" ")
+    }
+
+    pub fn from_notation(text: &str) -> Result<Game, String> {
+        let mut parts = text.split_whitespace().peekable();
+
+        let mut rules: &'static dyn Rules = &Standard;
+        if let Some(name) = parts.next_if(|part| !part.contains('/')) {
+            rules = rules_by_name(name).ok_or_else(|| format!("unknown rules '{}'", name))?;
+        }
+
+        let header = parts.next().ok_or("empty notation")?;
+        let (size, win_length) = parse_header(header)?;
+        if !rules.fits(size, win_length) {
+            return Err(format!("{} cannot be played on a {} board", rules.name(), header));
+        }
+
+        let board_text = parts.next().ok_or("missing board")?;
+        let board = parse_board(board_text, size)?;
+
+        let mut tokens: Vec<&str> = parts.collect();
+        let timeout = match tokens.last().and_then(|token| token.strip_prefix("timeout:")) {
+            Some(player) => {
+                let player = parse_player(player).ok_or_else(|| format!("invalid player '{}' in timeout", player))?;
+                tokens.pop();
+                Some(player)
+            }
+            None => None,
+        };
+        let moves = tokens
+            .into_iter()
+            .map(|token| parse_move(token, size).map(|(player, row, col)| (token, player, row, col)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // The pieces the moves don't account for were there from the start.
+        let mut start = board.clone();
+        for &(_, _, row, col) in &moves {
+            start[row][col] = CellState::Empty;
+        }
+        let count = |player: Player| start.iter().flatten().filter(|&&cell| cell == CellState::Occupied(player)).count();
+        let pieces = count(Player::X) + count(Player::O);
+        // The player to move next: the first move's, or on a position alone
+        // the one with fewer pieces, X if neither.
+        let to_move = match moves.first() {
+            Some(&(_, player, _, _)) => player,
+            None if rules.players_own_marks() && count(Player::O) > count(Player::X) => Player::X,
+            None if rules.players_own_marks() && count(Player::X) > count(Player::O) => Player::O,
+            None if rules.players_own_marks() || pieces.is_multiple_of(2) => Player::X,
+            None => Player::O,
+        };
+        // Rounds alternate who starts; with players taking turns, the number
+        // of pieces says who that was.
+        let first_player = if pieces.is_multiple_of(2) { to_move } else { to_move.other() };
+        // Piece counts only say whose turn it is when marks belong to players.
+        if rules.players_own_marks() {
+            check_position(&board, win_length, first_player)?;
+            check_position(&start, win_length, first_player)?;
+        }
+
+        let mut game = Game::from_position(start, win_length, rules, first_player);
+        for (i, &(token, player, row, col)) in moves.iter().enumerate() {
+            if player != game.current_player {
+                return Err(format!(
+                    "move {} ({}) is out of turn, expected {} to move",
+                    i + 1,
+                    token,
+                    player_char(game.current_player)
+                ));
+            }
+            let moved = match board[row][col] {
+                CellState::Occupied(mark) => game.make_move_as(row, col, mark),
+                CellState::Empty => game.make_move(row, col),
+            };
+            if !moved {
+                return Err(format!(
+                    "move {} ({}) is illegal: the cell is taken or closed, or the game is over",
+                    i + 1,
+                    token
+                ));
+            }
+        }
+
+        if game.board != board {
+            return Err("board does not match the position reached by the moves".to_string());
+        }
+
+        if let Some(player) = timeout {
+            if !matches!(game.game_state, GameState::Playing) || player != game.current_player {
+                return Err(format!("{} cannot lose on time here: it is not their move", player_char(player)));
+            }
+            game.game_state = GameState::Timeout(player);
+        }
+
+        Ok(game)
+    }
+}
+
+fn parse_header(header: &str) -> Result<(usize, usize), String> {
+    let (size, win_length) = header
+        .split_once('/')
+        .ok_or_else(|| format!("invalid header '{}', expected <size>/<win length>", header))?;
+    let size: usize = size
+        .parse()
+        .map_err(|_| format!("invalid board size '{}'", size))?;
+    let win_length: usize = win_length
+        .parse()
+        .map_err(|_| format!("invalid win length '{}'", win_length))?;
+
+    if !(DEFAULT_WIN_LENGTH..=MAX_BOARD_SIZE).contains(&size) {
+        return Err(format!(
+            "board size {} is outside {}..={}",
+            size, DEFAULT_WIN_LENGTH, MAX_BOARD_SIZE
+        ));
+    }
+    if !(DEFAULT_WIN_LENGTH..=size).contains(&win_length) {
+        return Err(format!(
+            "win length {} is outside {}..={}",
+            win_length, DEFAULT_WIN_LENGTH, size
+        ));
+    }
+    Ok((size, win_length))
+}
+
+fn parse_board(text: &str, size: usize) -> Result<Vec<Vec<CellState>>, String> {
+    let rows: Vec<&str> = text.split('/').collect();
+    if rows.len() != size {
+        return Err(format!("board has {} rows, expected {}", rows.len(), size));
+    }
+
+    rows.iter()
+        .enumerate()
+        .map(|(row, cells)| {
+            if cells.chars().count() != size {
+                return Err(format!(
+                    "board row {} has {} cells, expected {}",
+                    row + 1,
+                    cells.chars().count(),
+                    size
+                ));
+            }
+            cells
+                .chars()
+                .map(|c| match c {
+                    'X' => Ok(CellState::Occupied(Player::X)),
+                    'O' => Ok(CellState::Occupied(Player::O)),
+                    '.' => Ok(CellState::Empty),
+                    _ => Err(format!("invalid cell '{}' in board row {}", c, row + 1)),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Rejects boards that no game could reach: players take turns, so the
+/// first player has either as many pieces as the second or one more, at most
+/// one player can have a line, and the winner must have made the last move.
+fn check_position(board: &[Vec<CellState>], win_length: usize, first_player: Player) -> Result<(), String> {
+    let count = |player: Player| {
+        board
+            .iter()
+            .flatten()
+            .filter(|&&cell| cell == CellState::Occupied(player))
+            .count()
+    };
+    let second_player = first_player.other();
+    let (first_count, second_count) = (count(first_player), count(second_player));
+
+    if first_count != second_count && first_count != second_count + 1 {
+        return Err(format!(
+            "impossible piece counts: {} {} and {} {} with {} moving first",
+            first_count,
+            player_char(first_player),
+            second_count,
+            player_char(second_player),
+            player_char(first_player)
+        ));
+    }
+
+    let mut position = Game::new(board.len(), win_length);
+    position.board = board.to_vec();
+    let has_line = |player: Player| {
+        (0..board.len()).any(|row| {
+            (0..board.len()).any(|col| {
+                matches!(position.line_through(row, col), Some((p, _)) if p == player)
+            })
+        })
+    };
+
+    // Whoever made the last move is the only one who can have a line.
+    let (last_mover, waiting) = if first_count == second_count {
+        (second_player, first_player)
+    } else {
+        (first_player, second_player)
+    };
+
+    match (has_line(last_mover), has_line(waiting)) {
+        (true, true) => Err("impossible position: both players have a line".to_string()),
+        (_, true) => Err(format!(
+            "impossible position: {} has a line but {} moved last",
+            player_char(waiting),
+            player_char(last_mover)
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn parse_move(token: &str, size: usize) -> Result<(Player, usize, usize), String> {
+    let (player, cell) = token
+        .split_once(':')
+        .ok_or_else(|| format!("invalid move '{}', expected <player>:<cell>", token))?;
+    let player = parse_player(player).ok_or_else(|| format!("invalid player '{}' in move '{}'", player, token))?;
+    let (row, col) = parse_cell(cell, size)
+        .ok_or_else(|| format!("invalid cell '{}' in move '{}'", cell, token))?;
+    Ok((player, row, col))
+}
+
+fn parse_player(text: &str) -> Option<Player> {
+    match text {
+        "X" => Some(Player::X),
+        "O" => Some(Player::O),
+        _ => None,
+    }
+}
+
+/// Reads a cell name like `b2` on a `size`x`size` board as `(row, col)`.
+pub fn parse_cell(cell: &str, size: usize) -> Option<(usize, usize)> {
+    let mut chars = cell.chars();
+    let letter = chars.next()?;
+    if !letter.is_ascii_lowercase() {
+        return None;
+    }
+    let col = (letter as u8 - b'a') as usize;
+    let row = chars.as_str().parse::<usize>().ok()?.checked_sub(1)?;
+    (row < size && col < size).then_some((row, col))
+}
+
+/// The name of `(row, col)`: its column letter and its row counted from 1.
+pub fn cell_name(row: usize, col: usize) -> String {
+    format!("{}{}", (b'a' + col as u8) as char, row + 1)
+}
+
+fn cell_char(cell: CellState) -> char {
+    match cell {
+        CellState::Occupied(player) => player_char(player),
+        CellState::Empty => '.',
+    }
+}
+
+fn player_char(player: Player) -> char {
+    match player {
+        Player::X => 'X',
+        Player::O => 'O',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(text: &str) -> String {
+        match Game::from_notation(text) {
+            Ok(_) => panic!("'{}' should not load", text),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_notation() {
+        let mut game = Game::new(3, 3);
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.make_move(row, col);
+        }
+
+        let text = game.to_notation();
+        assert_eq!(text, "3/3 XXX/OO./... X:a1 O:a2 X:b1 O:b2 X:c1");
+
+        let loaded = Game::from_notation(&text).unwrap();
+        assert_eq!(loaded.to_notation(), text);
+        assert_eq!(loaded.history.len(), 5);
+        assert!(matches!(loaded.game_state, GameState::Win(Player::X, _)));
+    }
+
+    #[test]
+    fn round_trips_round_started_by_o() {
+        let mut game = Game::new(3, 3);
+        game.reset();
+        for (row, col) in [(1, 1), (0, 0), (2, 2)] {
+            game.make_move(row, col);
+        }
+
+        let text = game.to_notation();
+        assert_eq!(text, "3/3 X../.O./..O O:b2 X:a1 O:c3");
+
+        let loaded = Game::from_notation(&text).unwrap();
+        assert_eq!(loaded.first_player, Player::O);
+        assert_eq!(loaded.current_player, Player::X);
+        assert_eq!(loaded.to_notation(), text);
+    }
+
+    #[test]
+    fn loads_empty_gomoku_board() {
+        let text = format!("15/5 {}", vec![".".repeat(15); 15].join("/"));
+        let game = Game::from_notation(&text).unwrap();
+        assert_eq!(game.size(), 15);
+        assert_eq!(game.win_length, 5);
+    }
+
+    #[test]
+    fn rejects_out_of_turn_moves() {
+        let err = load_error("3/3 XO./.../... X:a1 X:b1");
+        assert!(err.contains("out of turn"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_moves_on_taken_cells() {
+        let err = load_error("3/3 X../.../... X:a1 O:a1");
+        assert!(err.contains("illegal"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_moves_after_the_game_ends() {
+        let err = load_error("3/3 XXX/OO./... X:a1 O:a2 X:b1 O:b2 X:c1 O:c2");
+        assert!(err.contains("move 6"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_impossible_wins() {
+        let err = load_error("3/3 XXX/OOO/... X:a1 O:a2 X:b1 O:b2 X:c1 O:c2");
+        assert!(err.contains("both players"), "{}", err);
+
+        let err = load_error("3/3 XXX/OO./O..");
+        assert!(err.contains("X has a line but O moved last"), "{}", err);
+
+        let err = load_error("3/3 XX./OOO/XX.");
+        assert!(err.contains("O has a line but X moved last"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_boards_the_rules_do_not_fit() {
+        let err = load_error("notakto 4/3 ..../..../..../....");
+        assert!(err.contains("notakto cannot be played on a 4/3 board"), "{}", err);
+        assert!(Game::from_notation("4/3 ..../..../..../....").is_ok());
+    }
+
+    #[test]
+    fn rejects_wrong_piece_counts() {
+        let err = load_error("3/3 XX./.../...");
+        assert!(err.contains("piece counts"), "{}", err);
+
+        let err = load_error("3/3 OO./.../...");
+        assert!(err.contains("piece counts"), "{}", err);
+    }
+
+    #[test]
+    fn loads_positions_without_moves() {
+        // O went first: one O and no X is a position after one move.
+        let game = Game::from_notation("3/3 O../.../...").unwrap();
+        assert_eq!(game.first_player, Player::O);
+        assert_eq!(game.current_player, Player::X);
+        assert!(game.history.is_empty());
+
+        let mut game = Game::from_notation("3/3 XX./OO./...").unwrap();
+        assert_eq!(game.current_player, Player::X);
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert!(game.make_move(0, 2));
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+
+        // Saved with the moves played from the position, it loads back
+        // with undo stopping at the position.
+        let text = game.to_notation();
+        assert_eq!(text, "3/3 XXX/OO./... X:c1");
+        let mut loaded = Game::from_notation(&text).unwrap();
+        assert_eq!(loaded.position_after(0).board, Game::from_notation("3/3 XX./OO./...").unwrap().board);
+        assert!(loaded.undo());
+        assert!(!loaded.undo());
+
+        let finished = Game::from_notation("3/3 XXX/OO./...").unwrap();
+        assert!(matches!(finished.game_state, GameState::Win(Player::X, _)));
+        let full = Game::from_notation("3/3 XOX/XOO/OXX").unwrap();
+        assert!(matches!(full.game_state, GameState::Draw));
+    }
+
+    #[test]
+    fn keeps_losses_on_time() {
+        let mut game = Game::new(3, 3);
+        game.make_move(1, 1);
+        game.game_state = GameState::Timeout(Player::O);
+        let text = game.to_notation();
+        assert_eq!(text, "3/3 .../.X./... X:b2 timeout:O");
+        let loaded = Game::from_notation(&text).unwrap();
+        assert!(matches!(loaded.game_state, GameState::Timeout(Player::O)));
+        assert_eq!(loaded.to_notation(), text);
+
+        let err = load_error("3/3 .../.X./... X:b2 timeout:X");
+        assert!(err.contains("not their move"), "{}", err);
+        assert!(Game::from_notation("3/3 .../.../... timeout:Z").is_err());
+    }
+
+    #[test]
+    fn rejects_board_that_does_not_match_moves() {
+        let err = load_error("3/3 X../.O./... X:a1 O:a2");
+        assert!(err.contains("does not match"), "{}", err);
+    }
+
+    #[test]
+    fn round_trips_other_rules() {
+        let mut wild = Game::with_rules(3, 3, &crate::rules::Wild);
+        wild.make_move_as(1, 1, Player::O);
+        wild.make_move_as(0, 0, Player::O);
+        let text = wild.to_notation();
+        assert_eq!(text, "wild 3/3 O../.O./... X:b2 O:a1");
+        let loaded = Game::from_notation(&text).unwrap();
+        assert_eq!(loaded.rules.name(), "wild");
+        assert_eq!(loaded.history, wild.history);
+
+        let mut notakto = Game::with_rules(6, 3, &crate::rules::Notakto);
+        for (row, col) in [(0, 0), (0, 1), (0, 2), (4, 4)] {
+            notakto.make_move(row, col);
+        }
+        let loaded = Game::from_notation(&notakto.to_notation()).unwrap();
+        assert!(!loaded.is_open(1, 1));
+        assert_eq!(loaded.current_player, Player::X);
+    }
+
+    #[test]
+    fn rejects_unknown_rules() {
+        let err = load_error("chess 3/3 .../.../...");
+        assert!(err.contains("unknown rules"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(Game::from_notation("").is_err());
+        assert!(Game::from_notation("3 .../.../...").is_err());
+        assert!(Game::from_notation("3/4 .../.../...").is_err());
+        assert!(Game::from_notation("3/3 .../...").is_err());
+        assert!(Game::from_notation("3/3 ..../.../...").is_err());
+        assert!(Game::from_notation("3/3 .?./.../...").is_err());
+        assert!(Game::from_notation("3/3 .../.../... X:d1").is_err());
+        assert!(Game::from_notation("3/3 .../.../... X:a0").is_err());
+        assert!(Game::from_notation("3/3 .../.../... Z:a1").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Rule sets. The board, moves, undo and rendering are shared; a rule set
+//! only decides which marks a player may use, which cells are open and how
+//! the game stands after each move.
+//!
+//! - standard: a line of `win_length` wins.
+//! - misère: a line of `win_length` loses.
+//! - wild: each move puts down an X or an O, whoever completes a line of
+//!   either wins.
+//! - notakto: both players play X. The grid is split into boards of
+//!   `win_length` cells a side; a board with a line is dead, and whoever
+//!   kills the last board loses.
+
+use crate::{BOARD_PRESETS, CellState, Game, GameState, Player};
+
+pub trait Rules {
+    /// Name used in saved games and, upper-cased, on screen.
+    fn name(&self) -> &'static str;
+
+    /// The marks `player` may put down.
+    fn marks(&self, player: Player) -> &'static [Player] {
+        match player {
+            Player::X => &[Player::X],
+            Player::O => &[Player::O],
+        }
+    }
+
+    /// Whether every mark belongs to one player, as in the standard game.
+    fn players_own_marks(&self) -> bool {
+        self.marks(Player::X) == [Player::X] && self.marks(Player::O) == [Player::O]
+    }
+
+    /// Whether completing a line is good for whoever completes it.
+    fn line_wins(&self) -> bool {
+        true
+    }
+
+    /// Side of the separate boards the grid is split into, if it is split.
+    fn sub_board_size(&self, _game: &Game) -> Option<usize> {
+        None
+    }
+
+    /// Whether an empty cell may be played while the game is on.
+    fn is_open(&self, _game: &Game, _row: usize, _col: usize) -> bool {
+        true
+    }
+
+    /// How `game` stands after its player to move filled `(row, col)`.
+    fn judge(&self, game: &Game, row: usize, col: usize) -> GameState;
+
+    /// Whether these rules can be played on a board of `size` with lines
+    /// of `win_length`.
+    fn fits(&self, _size: usize, _win_length: usize) -> bool {
+        true
+    }
+
+    /// Board size and win length pairs these rules are played with.
+    fn presets(&self) -> &'static [(usize, usize)] {
+        &BOARD_PRESETS
+    }
+}
+
+pub struct Standard;
+pub struct Misere;
+pub struct Wild;
+pub struct Notakto;
+
+/// Every rule set, in the order they are cycled through in play.
+pub const RULE_SETS: [&dyn Rules; 4] = [&Standard, &Misere, &Wild, &Notakto];
+
+const NOTAKTO_PRESETS: [(usize, usize); 3] = [(3, 3), (6, 3), (9, 3)];
+
+pub fn rules_by_name(name: &str) -> Option<&'static dyn Rules> {
+    RULE_SETS.into_iter().find(|rules| rules.name() == name)
+}
+
+/// The rule set after `rules` in [`RULE_SETS`].
+pub fn next_rules(rules: &'static dyn Rules) -> &'static dyn Rules {
+    let current = RULE_SETS.iter().position(|other| other.name() == rules.name());
+    RULE_SETS[current.map_or(0, |i| (i + 1) % RULE_SETS.len())]
+}
+
+impl Rules for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+
+    fn judge(&self, game: &Game, row: usize, col: usize) -> GameState {
+        match game.line_through(row, col) {
+            Some((player, win_type)) => GameState::Win(player, win_type),
+            None if game.is_full() => GameState::Draw,
+            None => GameState::Playing,
+        }
+    }
+}
+
+impl Rules for Misere {
+    fn name(&self) -> &'static str {
+        "misere"
+    }
+
+    fn line_wins(&self) -> bool {
+        false
+    }
+
+    fn judge(&self, game: &Game, row: usize, col: usize) -> GameState {
+        match game.line_through(row, col) {
+            Some((player, win_type)) => GameState::Win(player.other(), win_type),
+            None if game.is_full() => GameState::Draw,
+            None => GameState::Playing,
+        }
+    }
+}
+
+impl Rules for Wild {
+    fn name(&self) -> &'static str {
+        "wild"
+    }
+
+    fn marks(&self, _player: Player) -> &'static [Player] {
+        &[Player::X, Player::O]
+    }
+
+    fn judge(&self, game: &Game, row: usize, col: usize) -> GameState {
+        match game.line_through(row, col) {
+            Some((_, win_type)) => GameState::Win(game.current_player, win_type),
+            None if game.is_full() => GameState::Draw,
+            None => GameState::Playing,
+        }
+    }
+}
+
+impl Notakto {
+    /// The board holding `(row, col)` as `(top row, left column, side)`.
+    fn board_of(game: &Game, row: usize, col: usize) -> (usize, usize, usize) {
+        let side = game.win_length;
+        (row / side * side, col / side * side, side)
+    }
+
+    fn is_dead(game: &Game, (top, left, side): (usize, usize, usize)) -> bool {
+        (top..(top + side).min(game.size())).any(|row| {
+            (left..(left + side).min(game.size()))
+                .any(|col| game.line_within(row, col, (top, left, side)).is_some())
+        })
+    }
+}
+
+impl Rules for Notakto {
+    fn name(&self) -> &'static str {
+        "notakto"
+    }
+
+    fn marks(&self, _player: Player) -> &'static [Player] {
+        &[Player::X]
+    }
+
+    fn line_wins(&self) -> bool {
+        false
+    }
+
+    fn sub_board_size(&self, game: &Game) -> Option<usize> {
+        Some(game.win_length)
+    }
+
+    /// The grid must split into whole boards: a board cut short by the edge
+    /// may be too small to ever die.
+    fn fits(&self, size: usize, win_length: usize) -> bool {
+        size.is_multiple_of(win_length)
+    }
+
+    fn is_open(&self, game: &Game, row: usize, col: usize) -> bool {
+        !Notakto::is_dead(game, Notakto::board_of(game, row, col))
+    }
+
+    fn judge(&self, game: &Game, row: usize, col: usize) -> GameState {
+        if let Some((_, win_type)) = game.line_within(row, col, Notakto::board_of(game, row, col)) {
+            let side = game.win_length;
+            let all_dead = (0..game.size()).step_by(side).all(|top| {
+                (0..game.size())
+                    .step_by(side)
+                    .all(|left| Notakto::is_dead(game, (top, left, side)))
+            });
+            if all_dead {
+                return GameState::Win(game.current_player.other(), win_type);
+            }
+        }
+
+        // Only on a grid the rules do not fit can the cells run out first.
+        let any_open = (0..game.size()).any(|row| {
+            (0..game.size()).any(|col| game.board[row][col] == CellState::Empty && self.is_open(game, row, col))
+        });
+        if any_open { GameState::Playing } else { GameState::Draw }
+    }
+
+    fn presets(&self) -> &'static [(usize, usize)] {
+        &NOTAKTO_PRESETS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut Game, moves: &[(usize, usize)]) {
+        for &(row, col) in moves {
+            assert!(game.make_move(row, col));
+        }
+    }
+
+    #[test]
+    fn misere_line_loses() {
+        let mut game = Game::with_rules(3, 3, &Misere);
+        play(&mut game, &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        assert!(matches!(game.game_state, GameState::Win(Player::O, _)));
+
+        let mut game = Game::with_rules(3, 3, &Misere);
+        play(
+            &mut game,
+            &[(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0), (2, 2)],
+        );
+        assert!(matches!(game.game_state, GameState::Draw));
+    }
+
+    #[test]
+    fn wild_line_of_either_mark_wins_for_mover() {
+        let mut game = Game::with_rules(3, 3, &Wild);
+        assert!(game.make_move_as(0, 0, Player::O));
+        assert!(game.make_move_as(1, 1, Player::X));
+        assert!(game.make_move_as(2, 2, Player::X));
+        assert!(game.make_move_as(0, 1, Player::O));
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert!(game.make_move_as(0, 2, Player::O));
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+
+        game.undo();
+        assert_eq!(game.current_player, Player::X);
+        assert!(game.redo());
+        assert!(game.board[0][2] == CellState::Occupied(Player::O));
+    }
+
+    #[test]
+    fn standard_rules_only_allow_own_mark() {
+        let mut game = Game::new(3, 3);
+        assert!(!game.make_move_as(0, 0, Player::O));
+        assert!(game.make_move_as(0, 0, Player::X));
+    }
+
+    #[test]
+    fn notakto_boards_die_and_last_line_loses() {
+        let mut game = Game::with_rules(6, 3, &Notakto);
+        // Kill the top-left board; a line across two boards does not count.
+        play(&mut game, &[(0, 1), (0, 2), (0, 3), (0, 0)]);
+        assert!(!game.is_open(1, 1));
+        assert!(!game.make_move(1, 1));
+        assert!(game.is_open(0, 4));
+        assert!(matches!(game.game_state, GameState::Playing));
+
+        play(&mut game, &[(0, 4), (0, 5), (3, 0), (3, 1), (3, 2), (3, 3), (3, 4)]);
+        assert!(matches!(game.game_state, GameState::Playing));
+        assert!(game.make_move(3, 5));
+        assert!(game.history.iter().all(|next| next.mark == Player::X));
+        // O killed the last board with the twelfth move.
+        assert!(matches!(game.game_state, GameState::Win(Player::X, _)));
+    }
+
+    #[test]
+    fn notakto_ends_when_no_cell_is_left() {
+        assert!(!Notakto.fits(4, 3));
+        assert!(Notakto.presets().iter().all(|&(size, win_length)| Notakto.fits(size, win_length)));
+
+        // The boards on the right and bottom edges are too narrow to die.
+        let mut game = Game::with_rules(4, 3, &Notakto);
+        for row in 0..4 {
+            for col in 0..4 {
+                if game.is_open(row, col) {
+                    assert!(game.make_move(row, col));
+                }
+            }
+        }
+        assert!(matches!(game.game_state, GameState::Draw));
+    }
+
+    #[test]
+    fn rule_sets_cycle_by_name() {
+        assert_eq!(rules_by_name("misere").map(|rules| rules.name()), Some("misere"));
+        assert!(rules_by_name("chess").is_none());
+        assert_eq!(next_rules(&Notakto).name(), "standard");
+        assert_eq!(next_rules(&Standard).name(), "misere");
+    }
+}
@@ -0,0 +1,267 @@
+//! Exact game values by searching every line of play to the end. Only
+//! positions with a few moves left are small enough to solve; the solver
+//! plays through [`Game`] so it follows whichever rules the game uses.
+
+use std::collections::HashMap;
+
+use crate::{CellState, Game, GameState, Player};
+
+/// Positions with at most this many moves to choose from are solved. A move
+/// is a cell and a mark, so rules that let players pick a mark solve half
+/// as many empty cells.
+pub const SOLVE_LIMIT: usize = 10;
+
+/// How a position ends with perfect play, for the player to move in it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl Outcome {
+    /// The same result seen from the other player.
+    pub fn flip(self) -> Outcome {
+        match self {
+            Outcome::Win => Outcome::Loss,
+            Outcome::Draw => Outcome::Draw,
+            Outcome::Loss => Outcome::Win,
+        }
+    }
+}
+
+/// The outcome for the player to move if they put `mark` on `(row, col)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hint {
+    pub row: usize,
+    pub col: usize,
+    pub mark: Player,
+    pub outcome: Outcome,
+}
+
+type Position = (Vec<Vec<CellState>>, Player);
+
+/// Every move open to the player to move, annotated with how it ends under
+/// perfect play. `None` if there are more than [`SOLVE_LIMIT`] moves.
+pub fn hints(game: &Game) -> Option<Vec<Hint>> {
+    let moves = moves(game);
+    if moves.len() > SOLVE_LIMIT {
+        return None;
+    }
+
+    let mut solved = HashMap::new();
+    let hints = moves
+        .into_iter()
+        .map(|(row, col, mark)| {
+            let mut next = game.clone();
+            next.make_move_as(row, col, mark);
+            let outcome = outcome(&next, &mut solved).flip();
+            Hint { row, col, mark, outcome }
+        })
+        .collect();
+    Some(hints)
+}
+
+/// How `game` ends under perfect play for its player to move, or `None` if
+/// it has more than [`SOLVE_LIMIT`] moves left to choose from.
+pub fn solve(game: &Game) -> Option<Outcome> {
+    if moves(game).len() > SOLVE_LIMIT {
+        return None;
+    }
+    Some(outcome(game, &mut HashMap::new()))
+}
+
+fn moves(game: &Game) -> Vec<(usize, usize, Player)> {
+    let mut moves = Vec::new();
+    for row in 0..game.size() {
+        for col in 0..game.size() {
+            if game.is_open(row, col) {
+                moves.extend(game.marks().iter().map(|&mark| (row, col, mark)));
+            }
+        }
+    }
+    moves
+}
+
+/// Negamax over every move, remembering positions already solved since
+/// different move orders often reach the same board.
+fn outcome(game: &Game, solved: &mut HashMap<Position, Outcome>) -> Outcome {
+    match game.game_state {
+        GameState::Win(winner, _) if winner == game.current_player => return Outcome::Win,
+        GameState::Win(_, _) => return Outcome::Loss,
+        GameState::Draw => return Outcome::Draw,
+        GameState::Timeout(loser) if loser == game.current_player => return Outcome::Loss,
+        GameState::Timeout(_) => return Outcome::Win,
+        GameState::Playing => {}
+    }
+
+    let position = (game.board.clone(), game.current_player);
+    if let Some(&known) = solved.get(&position) {
+        return known;
+    }
+
+    let mut best = Outcome::Loss;
+    for (row, col, mark) in moves(game) {
+        let mut next = game.clone();
+        next.make_move_as(row, col, mark);
+        match outcome(&next, solved).flip() {
+            Outcome::Win => {
+                best = Outcome::Win;
+                break;
+            }
+            Outcome::Draw => best = Outcome::Draw,
+            Outcome::Loss => {}
+        }
+    }
+
+    solved.insert(position, best);
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Misere, Notakto, Wild};
+    use std::collections::HashSet;
+
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    /// Standard 3x3 play worked out on a bare array, sharing no code with
+    /// the rules module. Returns the outcome for `to_move`.
+    fn brute_force(cells: &mut [Option<Player>; 9], to_move: Player) -> Outcome {
+        let mut best = Outcome::Loss;
+        let mut any_move = false;
+        for i in 0..9 {
+            if cells[i].is_some() {
+                continue;
+            }
+            any_move = true;
+            cells[i] = Some(to_move);
+            let won = LINES
+                .iter()
+                .any(|line| line.contains(&i) && line.iter().all(|&j| cells[j] == Some(to_move)));
+            let result = if won {
+                Outcome::Win
+            } else if cells.iter().all(Option::is_some) {
+                Outcome::Draw
+            } else {
+                brute_force(cells, to_move.other()).flip()
+            };
+            cells[i] = None;
+            match result {
+                Outcome::Win => return Outcome::Win,
+                Outcome::Draw => best = Outcome::Draw,
+                Outcome::Loss => {}
+            }
+        }
+        if any_move { best } else { Outcome::Draw }
+    }
+
+    fn cells_of(game: &Game) -> [Option<Player>; 9] {
+        let mut cells = [None; 9];
+        for (i, cell) in cells.iter_mut().enumerate() {
+            if let CellState::Occupied(player) = game.board[i / 3][i % 3] {
+                *cell = Some(player);
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn hints_agree_with_brute_force_in_every_position() {
+        fn visit(game: &Game, visited: &mut HashSet<Vec<Vec<CellState>>>) {
+            if !matches!(game.game_state, GameState::Playing) || !visited.insert(game.board.clone()) {
+                return;
+            }
+
+            for hint in hints(game).unwrap() {
+                let mut cells = cells_of(game);
+                cells[hint.row * 3 + hint.col] = Some(game.current_player);
+                let won = LINES.iter().any(|line| line.iter().all(|&j| cells[j] == Some(game.current_player)));
+                let expected = if won {
+                    Outcome::Win
+                } else if cells.iter().all(Option::is_some) {
+                    Outcome::Draw
+                } else {
+                    brute_force(&mut cells, game.current_player.other()).flip()
+                };
+                assert_eq!(hint.outcome, expected, "{:?} in {:?}", hint, cells_of(game));
+
+                let mut next = game.clone();
+                next.make_move(hint.row, hint.col);
+                visit(&next, visited);
+            }
+        }
+
+        // Every unfinished position reachable in the 3x3 game.
+        let mut visited = HashSet::new();
+        visit(&Game::new(3, 3), &mut visited);
+        assert_eq!(visited.len(), 4520);
+    }
+
+    #[test]
+    fn empty_board_is_a_draw_from_every_cell() {
+        let game = Game::new(3, 3);
+        assert_eq!(solve(&game), Some(Outcome::Draw));
+        let hints = hints(&game).unwrap();
+        assert_eq!(hints.len(), 9);
+        assert!(hints.iter().all(|hint| hint.outcome == Outcome::Draw));
+    }
+
+    #[test]
+    fn corner_reply_to_opposite_corners_loses() {
+        let mut game = Game::new(3, 3);
+        for (row, col) in [(0, 0), (1, 1), (2, 2)] {
+            game.make_move(row, col);
+        }
+        // O must take an edge; a corner lets X fork.
+        for hint in hints(&game).unwrap() {
+            let corner = hint.row != 1 && hint.col != 1;
+            let expected = if corner { Outcome::Loss } else { Outcome::Draw };
+            assert_eq!(hint.outcome, expected, "{:?}", hint);
+        }
+    }
+
+    #[test]
+    fn solves_other_rule_sets() {
+        // The first player wins notakto on one board by taking the center.
+        assert_eq!(solve(&Game::with_rules(3, 3, &Notakto)), Some(Outcome::Win));
+        assert_eq!(solve(&Game::with_rules(3, 3, &Misere)), Some(Outcome::Draw));
+
+        // Wild play offers both marks on every cell, so the open board is
+        // too big, but with four cells left either mark completes the
+        // diagonal.
+        let mut game = Game::with_rules(3, 3, &Wild);
+        assert_eq!(solve(&game), None);
+        let moves = [
+            (0, 0, Player::X),
+            (0, 1, Player::O),
+            (1, 2, Player::X),
+            (2, 0, Player::O),
+            (2, 2, Player::X),
+        ];
+        for (row, col, mark) in moves {
+            assert!(game.make_move_as(row, col, mark));
+        }
+        let hints = hints(&game).unwrap();
+        assert_eq!(hints.len(), 8);
+        assert!(hints.contains(&Hint { row: 1, col: 1, mark: Player::X, outcome: Outcome::Win }));
+        assert_eq!(solve(&game), Some(Outcome::Win));
+    }
+
+    #[test]
+    fn large_positions_are_not_solved() {
+        let game = Game::new(9, 5);
+        assert_eq!(solve(&game), None);
+        assert!(hints(&game).is_none());
+    }
+}
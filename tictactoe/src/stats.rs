@@ -0,0 +1,263 @@
+//! Win/draw counts for the current session and across restarts.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tictactoe::{GameState, Player};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Win(Player),
+    Draw,
+}
+
+impl Outcome {
+    pub fn of(game_state: &GameState) -> Option<Outcome> {
+        match game_state {
+            GameState::Playing => None,
+            GameState::Win(player, _) => Some(Outcome::Win(*player)),
+            GameState::Draw => Some(Outcome::Draw),
+            GameState::Timeout(player) => Some(Outcome::Win(player.other())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Score {
+    pub x_wins: u32,
+    pub o_wins: u32,
+    pub draws: u32,
+}
+
+impl Score {
+    fn counter(&mut self, outcome: Outcome) -> &mut u32 {
+        match outcome {
+            Outcome::Win(Player::X) => &mut self.x_wins,
+            Outcome::Win(Player::O) => &mut self.o_wins,
+            Outcome::Draw => &mut self.draws,
+        }
+    }
+
+    pub fn add(&mut self, outcome: Outcome) {
+        *self.counter(outcome) += 1;
+    }
+
+    pub fn remove(&mut self, outcome: Outcome) {
+        let counter = self.counter(outcome);
+        *counter = counter.saturating_sub(1);
+    }
+
+    pub fn to_text(self) -> String {
+        format!(
+            "x_wins={}\no_wins={}\ndraws={}\n",
+            self.x_wins, self.o_wins, self.draws
+        )
+    }
+
+    pub fn parse(text: &str) -> Option<Score> {
+        let (mut x_wins, mut o_wins, mut draws) = (None, None, None);
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once('=')?;
+            let value: u32 = value.trim().parse().ok()?;
+            let field = match key.trim() {
+                "x_wins" => &mut x_wins,
+                "o_wins" => &mut o_wins,
+                "draws" => &mut draws,
+                _ => return None,
+            };
+            if field.replace(value).is_some() {
+                return None;
+            }
+        }
+        Some(Score {
+            x_wins: x_wins?,
+            o_wins: o_wins?,
+            draws: draws?,
+        })
+    }
+
+    /// Reads the score saved at `path`. A missing or unreadable file is not
+    /// fatal: a warning is printed and counting starts again from zero.
+    pub fn load(path: &Path) -> Score {
+        match fs::read_to_string(path) {
+            Ok(text) => Score::parse(&text).unwrap_or_else(|| {
+                eprintln!("Stats file {} is corrupted, starting from zero", path.display());
+                Score::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("No stats file at {}, starting from zero", path.display());
+                Score::default()
+            }
+            Err(e) => {
+                eprintln!("Stats load failed: {}, starting from zero", e);
+                Score::default()
+            }
+        }
+    }
+
+    pub fn save(self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+}
+
+/// Keeps the session and lifetime scores in step with the game on screen.
+/// The result of a round is counted once when it ends, and taken back if the
+/// finishing move is undone.
+pub struct Scoreboard {
+    pub session: Score,
+    pub lifetime: Score,
+    path: Option<PathBuf>,
+    counted: Option<Outcome>,
+    ignored: Option<Outcome>,
+}
+
+impl Scoreboard {
+    pub fn load(path: PathBuf) -> Scoreboard {
+        Scoreboard {
+            session: Score::default(),
+            lifetime: Score::load(&path),
+            path: Some(path),
+            counted: None,
+            ignored: None,
+        }
+    }
+
+    /// Starts counting a new round. A result the round already has (a
+    /// finished game loaded from a file) is not counted.
+    pub fn start_round(&mut self, game_state: &GameState) {
+        self.counted = None;
+        self.ignored = Outcome::of(game_state);
+    }
+
+    pub fn update(&mut self, game_state: &GameState) {
+        let outcome = Outcome::of(game_state).filter(|&outcome| Some(outcome) != self.ignored);
+        if outcome == self.counted {
+            return;
+        }
+
+        if let Some(previous) = self.counted {
+            self.session.remove(previous);
+            self.lifetime.remove(previous);
+        }
+        if let Some(outcome) = outcome {
+            self.session.add(outcome);
+            self.lifetime.add(outcome);
+        }
+        self.counted = outcome;
+
+        if let Some(path) = &self.path
+            && let Err(e) = self.lifetime.save(path)
+        {
+            eprintln!("Stats save failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tictactoe::Game;
+
+    fn scoreboard() -> Scoreboard {
+        Scoreboard {
+            session: Score::default(),
+            lifetime: Score { x_wins: 5, o_wins: 2, draws: 1 },
+            path: None,
+            counted: None,
+            ignored: None,
+        }
+    }
+
+    #[test]
+    fn score_round_trips_through_text() {
+        let score = Score { x_wins: 3, o_wins: 0, draws: 12 };
+        assert_eq!(Score::parse(&score.to_text()), Some(score));
+    }
+
+    #[test]
+    fn corrupted_scores_are_rejected() {
+        assert_eq!(Score::parse(""), None);
+        assert_eq!(Score::parse("x_wins=1\no_wins=2"), None);
+        assert_eq!(Score::parse("x_wins=1\no_wins=2\ndraws=-1"), None);
+        assert_eq!(Score::parse("x_wins=1\no_wins=2\ndraws=3\ndraws=4"), None);
+        assert_eq!(Score::parse("x_wins=1\no_wins=2\nties=3"), None);
+        assert_eq!(Score::parse("\u{0}\u{1}garbage"), None);
+    }
+
+    #[test]
+    fn missing_or_corrupted_file_loads_as_zero() {
+        let dir = std::env::temp_dir().join(format!("tictactoe-stats-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(Score::load(&dir.join("missing.txt")), Score::default());
+
+        let corrupted = dir.join("corrupted.txt");
+        fs::write(&corrupted, "x_wins=lots").unwrap();
+        assert_eq!(Score::load(&corrupted), Score::default());
+
+        // Saving creates the directory it goes in.
+        let saved = dir.join("tictactoe").join("saved.txt");
+        let score = Score { x_wins: 1, o_wins: 2, draws: 3 };
+        score.save(&saved).unwrap();
+        assert_eq!(Score::load(&saved), score);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finished_round_is_counted_once() {
+        let mut board = scoreboard();
+        let mut game = Game::new(3, 3);
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.make_move(row, col);
+            board.update(&game.game_state);
+        }
+        board.update(&game.game_state);
+
+        assert_eq!(board.session, Score { x_wins: 1, o_wins: 0, draws: 0 });
+        assert_eq!(board.lifetime, Score { x_wins: 6, o_wins: 2, draws: 1 });
+
+        game.reset();
+        board.start_round(&game.game_state);
+        board.update(&game.game_state);
+        assert_eq!(board.session.x_wins, 1);
+    }
+
+    #[test]
+    fn timeout_counts_as_a_win_for_the_other_player() {
+        let mut board = scoreboard();
+        board.update(&GameState::Timeout(Player::X));
+        assert_eq!(board.session, Score { x_wins: 0, o_wins: 1, draws: 0 });
+    }
+
+    #[test]
+    fn undoing_the_finishing_move_takes_the_result_back() {
+        let mut board = scoreboard();
+        let mut game = Game::new(3, 3);
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.make_move(row, col);
+        }
+        board.update(&game.game_state);
+
+        game.undo();
+        board.update(&game.game_state);
+        assert_eq!(board.session, Score::default());
+        assert_eq!(board.lifetime, Score { x_wins: 5, o_wins: 2, draws: 1 });
+    }
+
+    #[test]
+    fn already_finished_game_is_not_counted() {
+        let mut board = scoreboard();
+        let mut game = Game::new(3, 3);
+        for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.make_move(row, col);
+        }
+        board.start_round(&game.game_state);
+        board.update(&game.game_state);
+        assert_eq!(board.session, Score::default());
+    }
+}
@@ -0,0 +1,322 @@
+//! Colors, line widths and piece shapes the board is drawn with. A few themes are built
+//! in and cycled with T; `--theme` picks one by name or loads a TOML file
+//! such as `themes/solarized.toml`.
+//!
+//! Colors in a theme file are written as `"#rrggbb"` or `"#rrggbbaa"`, and
+//! anything a file leaves out is taken from the classic theme. `pieces`
+//! is `"lines"` or `"solid"`.
+
+use std::fs;
+
+use macroquad::prelude::Color;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use tictactoe::Player;
+
+/// How X and O are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PieceStyle {
+    /// X as two strokes, O as a ring.
+    #[default]
+    Lines,
+    /// X as two strokes twice as thick, O as a disc.
+    Solid,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub name: String,
+    #[serde(deserialize_with = "color")]
+    pub background: Color,
+    /// The panel behind the board and its outline.
+    #[serde(deserialize_with = "color")]
+    pub board: Color,
+    #[serde(deserialize_with = "color")]
+    pub board_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub grid: Color,
+    /// Lines inside the small boards of ultimate mode.
+    #[serde(deserialize_with = "color")]
+    pub inner_grid: Color,
+    /// The cell under the mouse or keyboard cursor.
+    #[serde(deserialize_with = "color")]
+    pub hover: Color,
+    /// Small boards the current player may move on in ultimate mode.
+    #[serde(deserialize_with = "color")]
+    pub highlight: Color,
+    #[serde(deserialize_with = "color")]
+    pub cursor: Color,
+    #[serde(deserialize_with = "color")]
+    pub x: Color,
+    #[serde(deserialize_with = "color")]
+    pub o: Color,
+    #[serde(deserialize_with = "color")]
+    pub title: Color,
+    /// The scoreboard.
+    #[serde(deserialize_with = "color")]
+    pub text: Color,
+    /// Key hints, messages and the status line while nobody has won.
+    #[serde(deserialize_with = "color")]
+    pub info: Color,
+    #[serde(deserialize_with = "color")]
+    pub error: Color,
+    /// Cell tints of the hint overlay for moves that win, draw and lose.
+    #[serde(deserialize_with = "color")]
+    pub hint_win: Color,
+    #[serde(deserialize_with = "color")]
+    pub hint_draw: Color,
+    #[serde(deserialize_with = "color")]
+    pub hint_loss: Color,
+    /// Buttons: fill, fill under the mouse, outline and label.
+    #[serde(deserialize_with = "color")]
+    pub button: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_hover: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_text: Color,
+    /// Buttons that cannot be pressed: fill, outline and label.
+    #[serde(deserialize_with = "color")]
+    pub button_disabled: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_disabled_border: Color,
+    #[serde(deserialize_with = "color")]
+    pub button_disabled_text: Color,
+    pub pieces: PieceStyle,
+    /// Grid line width on a full-size cell; pieces are drawn twice as thick.
+    pub line_thickness: f32,
+    pub win_line_thickness: f32,
+}
+
+impl Default for Theme {
+    /// The classic colors, under the name a theme file gets if it gives none.
+    fn default() -> Self {
+        Self {
+            name: "custom".to_string(),
+            background: Color::from_rgba(0, 0, 0, 255),
+            board: Color::from_rgba(20, 20, 20, 255),
+            board_border: Color::from_rgba(100, 100, 100, 255),
+            grid: Color::from_rgba(180, 180, 180, 255),
+            inner_grid: Color::from_rgba(100, 100, 100, 255),
+            hover: Color::from_rgba(40, 40, 40, 255),
+            highlight: Color::from_rgba(40, 50, 40, 255),
+            cursor: Color::from_rgba(241, 196, 15, 255),
+            x: Color::from_rgba(52, 152, 219, 255),
+            o: Color::from_rgba(231, 76, 60, 255),
+            title: Color::from_rgba(200, 200, 200, 255),
+            text: Color::from_rgba(150, 150, 150, 255),
+            info: Color::from_rgba(100, 100, 100, 255),
+            error: Color::from_rgba(231, 76, 60, 255),
+            hint_win: Color::from_rgba(46, 204, 113, 255),
+            hint_draw: Color::from_rgba(150, 150, 150, 255),
+            hint_loss: Color::from_rgba(192, 57, 43, 255),
+            button: Color::from_rgba(39, 174, 96, 255),
+            button_hover: Color::from_rgba(46, 204, 113, 255),
+            button_border: Color::from_rgba(30, 130, 76, 255),
+            button_text: Color::from_rgba(255, 255, 255, 255),
+            button_disabled: Color::from_rgba(60, 60, 60, 255),
+            button_disabled_border: Color::from_rgba(40, 40, 40, 255),
+            button_disabled_text: Color::from_rgba(130, 130, 130, 255),
+            pieces: PieceStyle::Lines,
+            line_thickness: 6.0,
+            win_line_thickness: 10.0,
+        }
+    }
+}
+
+impl Theme {
+    /// The built-in themes, in the order T cycles through them.
+    pub fn built_in() -> Vec<Theme> {
+        vec![Theme::classic(), Theme::paper(), Theme::high_contrast()]
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::built_in().into_iter().find(|theme| theme.name == name)
+    }
+
+    pub fn classic() -> Theme {
+        Theme {
+            name: "classic".to_string(),
+            ..Theme::default()
+        }
+    }
+
+    /// Dark ink on a light background.
+    pub fn paper() -> Theme {
+        Theme {
+            name: "paper".to_string(),
+            background: Color::from_rgba(244, 241, 232, 255),
+            board: Color::from_rgba(255, 253, 247, 255),
+            board_border: Color::from_rgba(184, 178, 163, 255),
+            grid: Color::from_rgba(92, 90, 85, 255),
+            inner_grid: Color::from_rgba(168, 163, 151, 255),
+            hover: Color::from_rgba(235, 230, 216, 255),
+            highlight: Color::from_rgba(227, 239, 217, 255),
+            cursor: Color::from_rgba(217, 142, 4, 255),
+            x: Color::from_rgba(31, 95, 168, 255),
+            o: Color::from_rgba(192, 57, 43, 255),
+            title: Color::from_rgba(58, 56, 52, 255),
+            text: Color::from_rgba(107, 103, 95, 255),
+            info: Color::from_rgba(138, 133, 122, 255),
+            error: Color::from_rgba(192, 57, 43, 255),
+            hint_win: Color::from_rgba(39, 174, 96, 255),
+            hint_draw: Color::from_rgba(138, 133, 122, 255),
+            hint_loss: Color::from_rgba(192, 57, 43, 255),
+            button: Color::from_rgba(39, 174, 96, 255),
+            button_hover: Color::from_rgba(46, 204, 113, 255),
+            button_border: Color::from_rgba(30, 130, 76, 255),
+            button_text: Color::from_rgba(255, 255, 255, 255),
+            button_disabled: Color::from_rgba(227, 222, 209, 255),
+            button_disabled_border: Color::from_rgba(184, 178, 163, 255),
+            button_disabled_text: Color::from_rgba(138, 133, 122, 255),
+            ..Theme::default()
+        }
+    }
+
+    /// White lines on black with thicker strokes and solid pieces. X and O
+    /// are sky blue and orange from the Okabe-Ito palette, which stay apart
+    /// under the common kinds of color blindness.
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high-contrast".to_string(),
+            background: Color::from_rgba(0, 0, 0, 255),
+            board: Color::from_rgba(0, 0, 0, 255),
+            board_border: Color::from_rgba(255, 255, 255, 255),
+            grid: Color::from_rgba(255, 255, 255, 255),
+            inner_grid: Color::from_rgba(191, 191, 191, 255),
+            hover: Color::from_rgba(64, 64, 64, 255),
+            highlight: Color::from_rgba(0, 58, 89, 255),
+            cursor: Color::from_rgba(240, 228, 66, 255),
+            x: Color::from_rgba(86, 180, 233, 255),
+            o: Color::from_rgba(230, 159, 0, 255),
+            title: Color::from_rgba(255, 255, 255, 255),
+            text: Color::from_rgba(255, 255, 255, 255),
+            info: Color::from_rgba(221, 221, 221, 255),
+            error: Color::from_rgba(213, 94, 0, 255),
+            hint_win: Color::from_rgba(0, 158, 115, 255),
+            hint_draw: Color::from_rgba(191, 191, 191, 255),
+            hint_loss: Color::from_rgba(213, 94, 0, 255),
+            button: Color::from_rgba(0, 114, 178, 255),
+            button_hover: Color::from_rgba(86, 180, 233, 255),
+            button_border: Color::from_rgba(255, 255, 255, 255),
+            button_text: Color::from_rgba(255, 255, 255, 255),
+            button_disabled: Color::from_rgba(0, 0, 0, 255),
+            button_disabled_border: Color::from_rgba(128, 128, 128, 255),
+            button_disabled_text: Color::from_rgba(128, 128, 128, 255),
+            pieces: PieceStyle::Solid,
+            line_thickness: 8.0,
+            win_line_thickness: 14.0,
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Theme, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Theme, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Theme::from_toml(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// The color `player`'s pieces are drawn in, at `alpha` out of 255.
+    pub fn player(&self, player: Player, alpha: u8) -> Color {
+        let color = match player {
+            Player::X => self.x,
+            Player::O => self.o,
+        };
+        Color {
+            a: color.a * alpha as f32 / 255.0,
+            ..color
+        }
+    }
+
+    /// The background at `alpha`, laid over cells to dim them.
+    pub fn shade(&self, alpha: u8) -> Color {
+        Color {
+            a: alpha as f32 / 255.0,
+            ..self.background
+        }
+    }
+}
+
+/// Reads `#rrggbb` or `#rrggbbaa`.
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| format!("invalid color '{}', expected #rrggbb or #rrggbbaa", text))
+    };
+    match hex.len() {
+        6 => Ok(Color::from_rgba(channel(0)?, channel(2)?, channel(4)?, 255)),
+        8 => Ok(Color::from_rgba(channel(0)?, channel(2)?, channel(4)?, channel(6)?)),
+        _ => Err(format!("invalid color '{}', expected #rrggbb or #rrggbbaa", text)),
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_color(&text).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_themes_are_found_by_name() {
+        let themes = Theme::built_in();
+        for theme in &themes {
+            assert_eq!(Theme::by_name(&theme.name).as_ref(), Some(theme));
+        }
+        assert_eq!(Theme::by_name("classic").unwrap().x, Color::from_rgba(52, 152, 219, 255));
+        assert!(Theme::by_name("neon").is_none());
+    }
+
+    #[test]
+    fn theme_file_fills_gaps_from_classic() {
+        let theme = Theme::from_toml("name = \"mine\"\nx = \"#102030\"\no = \"#40506080\"\nline_thickness = 4.5\n").unwrap();
+        assert_eq!(theme.name, "mine");
+        assert_eq!(theme.x, Color::from_rgba(16, 32, 48, 255));
+        assert_eq!(theme.o, Color::from_rgba(64, 80, 96, 128));
+        assert_eq!(theme.line_thickness, 4.5);
+        assert_eq!(theme.grid, Theme::classic().grid);
+        assert_eq!(theme.button, Theme::classic().button);
+        assert_eq!(theme.pieces, PieceStyle::Lines);
+
+        let theme = Theme::from_toml("pieces = \"solid\"\nbutton_text = \"#000000\"\n").unwrap();
+        assert_eq!(theme.pieces, PieceStyle::Solid);
+        assert_eq!(theme.button_text, Color::from_rgba(0, 0, 0, 255));
+
+        let theme = Theme::from_toml("").unwrap();
+        assert_eq!(theme.name, "custom");
+    }
+
+    #[test]
+    fn bad_theme_files_are_rejected() {
+        let e = Theme::from_toml("x = \"#12345\"\n").unwrap_err();
+        assert!(e.contains("invalid color '#12345'"), "{}", e);
+        assert!(Theme::from_toml("x = \"#gg0000\"\n").is_err());
+        assert!(Theme::from_toml("pieces = \"round\"\n").is_err());
+        assert!(Theme::from_toml("line_thickness = \"thick\"\n").is_err());
+    }
+
+    #[test]
+    fn example_theme_file_loads() {
+        let theme = Theme::from_toml(include_str!("../themes/solarized.toml")).unwrap();
+        assert_eq!(theme.name, "solarized");
+        assert_ne!(theme.x, theme.o);
+    }
+
+    #[test]
+    fn player_color_scales_alpha() {
+        let theme = Theme::classic();
+        assert_eq!(theme.player(Player::O, 255), theme.o);
+        assert_eq!(theme.player(Player::X, 0).a, 0.0);
+        assert_eq!(theme.shade(150), Color::from_rgba(0, 0, 0, 150));
+    }
+}
@@ -0,0 +1,212 @@
+//! Headless tournaments: `tictactoe --tournament` plays every strategy
+//! against every other, itself included, from both sides of the board and
+//! prints the results as CSV for charting.
+//!
+//! The strategies are `random`, `first-empty`, `greedy` (the heuristic
+//! that wins, blocks, then takes the center and corners) and `perfect`
+//! (minimax). `--strategies` picks which play, `--games` how many games
+//! each pairing plays, `--seed` seeds the random moves, and `--size`,
+//! `--win` and `--rules` set the board as in normal play.
+//!
+//! Each row of the output is one pairing. `--matrix` prints the same
+//! results as a grid instead, X strategies down and O strategies across,
+//! each cell holding X's wins, draws and losses.
+
+use std::io::{self, Write};
+
+use tictactoe::{Game, GameState, Player};
+
+use crate::ai::Strategy;
+
+/// Results of one strategy playing X against another playing O.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pairing {
+    pub x: String,
+    pub o: String,
+    pub x_wins: u32,
+    pub o_wins: u32,
+    pub draws: u32,
+    /// Moves played over all the games.
+    pub moves: u32,
+}
+
+impl Pairing {
+    pub fn games(&self) -> u32 {
+        self.x_wins + self.o_wins + self.draws
+    }
+
+    pub fn average_length(&self) -> f64 {
+        self.moves as f64 / self.games().max(1) as f64
+    }
+}
+
+pub const CSV_HEADER: &str = "x,o,games,x_wins,o_wins,draws,x_win_rate,o_win_rate,draw_rate,average_moves";
+
+/// Plays `games` games of every ordered pairing of `strategies`, each one
+/// starting from a copy of `start`.
+pub fn run(strategies: &mut [Box<dyn Strategy>], start: &Game, games: u32) -> Vec<Pairing> {
+    let mut pairings = Vec::new();
+    for x in 0..strategies.len() {
+        for o in 0..strategies.len() {
+            let mut pairing = Pairing {
+                x: strategies[x].name(),
+                o: strategies[o].name(),
+                x_wins: 0,
+                o_wins: 0,
+                draws: 0,
+                moves: 0,
+            };
+            for _ in 0..games {
+                let (winner, moves) = play(strategies, x, o, start.clone());
+                match winner {
+                    Some(Player::X) => pairing.x_wins += 1,
+                    Some(Player::O) => pairing.o_wins += 1,
+                    None => pairing.draws += 1,
+                }
+                pairing.moves += moves;
+            }
+            pairings.push(pairing);
+        }
+    }
+    pairings
+}
+
+/// Plays one game and returns the winner, if any, and the number of moves.
+/// A strategy that passes or tries an illegal move forfeits.
+fn play(strategies: &mut [Box<dyn Strategy>], x: usize, o: usize, mut game: Game) -> (Option<Player>, u32) {
+    let mut moves = 0;
+    loop {
+        match game.game_state {
+            GameState::Win(player, _) => return (Some(player), moves),
+            GameState::Timeout(player) => return (Some(player.other()), moves),
+            GameState::Draw => return (None, moves),
+            GameState::Playing => {}
+        }
+
+        let player = game.current_player;
+        let strategy = match player {
+            Player::X => &mut strategies[x],
+            Player::O => &mut strategies[o],
+        };
+        match strategy.next_move(&game) {
+            Some((row, col, mark)) if game.make_move_as(row, col, mark) => moves += 1,
+            _ => return (Some(player.other()), moves),
+        }
+    }
+}
+
+pub fn write_csv(pairings: &[Pairing], output: &mut impl Write) -> io::Result<()> {
+    writeln!(output, "{}", CSV_HEADER)?;
+    for pairing in pairings {
+        let rate = |count: u32| count as f64 / pairing.games().max(1) as f64;
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{:.3},{:.3},{:.3},{:.2}",
+            pairing.x,
+            pairing.o,
+            pairing.games(),
+            pairing.x_wins,
+            pairing.o_wins,
+            pairing.draws,
+            rate(pairing.x_wins),
+            rate(pairing.o_wins),
+            rate(pairing.draws),
+            pairing.average_length(),
+        )?;
+    }
+    Ok(())
+}
+
+/// The results as a grid with a row per X strategy and a column per O
+/// strategy, each cell `wins/draws/losses` for X.
+pub fn write_matrix(pairings: &[Pairing], output: &mut impl Write) -> io::Result<()> {
+    let mut names: Vec<&str> = Vec::new();
+    for pairing in pairings {
+        if !names.contains(&pairing.o.as_str()) {
+            names.push(&pairing.o);
+        }
+    }
+
+    writeln!(output, "x,{}", names.join(","))?;
+    for x in &names {
+        let cells: Vec<String> = names
+            .iter()
+            .map(|o| match pairings.iter().find(|pairing| pairing.x == *x && pairing.o == *o) {
+                Some(pairing) => format!("{}/{}/{}", pairing.x_wins, pairing.draws, pairing.o_wins),
+                None => String::new(),
+            })
+            .collect();
+        writeln!(output, "{},{}", x, cells.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Difficulty, FirstEmpty};
+
+    fn strategies(names: &[&str]) -> Vec<Box<dyn Strategy>> {
+        names.iter().map(|name| crate::ai::strategy_by_name(name).unwrap()).collect()
+    }
+
+    #[test]
+    fn every_pairing_plays_every_game() {
+        let mut strategies = strategies(&["random", "first-empty", "greedy"]);
+        let pairings = run(&mut strategies, &Game::new(3, 3), 20);
+        assert_eq!(pairings.len(), 9);
+        for pairing in &pairings {
+            assert_eq!(pairing.games(), 20);
+            assert!((5.0..=9.0).contains(&pairing.average_length()), "{:?}", pairing);
+        }
+        assert_eq!((pairings[1].x.as_str(), pairings[1].o.as_str()), ("random", "first-empty"));
+    }
+
+    #[test]
+    fn first_empty_against_itself_is_a_diagonal_for_x() {
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(FirstEmpty)];
+        let pairings = run(&mut strategies, &Game::new(3, 3), 3);
+        assert_eq!(pairings[0].x_wins, 3);
+        assert_eq!(pairings[0].average_length(), 7.0);
+    }
+
+    #[test]
+    fn perfect_play_never_loses() {
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(Difficulty::Perfect), Box::new(FirstEmpty)];
+        let pairings = run(&mut strategies, &Game::new(3, 3), 2);
+        let result = |x: &str, o: &str| pairings.iter().find(|p| p.x == x && p.o == o).unwrap().clone();
+        assert_eq!(result("perfect", "perfect").draws, 2);
+        assert_eq!(result("perfect", "first-empty").x_wins, 2);
+        assert_eq!(result("first-empty", "perfect").o_wins, 2);
+    }
+
+    #[test]
+    fn matrix_has_a_row_per_x_strategy() {
+        let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(FirstEmpty), Box::new(Difficulty::Perfect)];
+        let pairings = run(&mut strategies, &Game::new(3, 3), 1);
+        let mut output = Vec::new();
+        write_matrix(&pairings, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "x,first-empty,perfect\nfirst-empty,1/0/0,0/0/1\nperfect,1/0/0,0/1/0\n"
+        );
+    }
+
+    #[test]
+    fn csv_has_a_row_per_pairing() {
+        let pairing = Pairing {
+            x: "greedy".to_string(),
+            o: "random".to_string(),
+            x_wins: 3,
+            o_wins: 0,
+            draws: 1,
+            moves: 26,
+        };
+        let mut output = Vec::new();
+        write_csv(&[pairing], &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}\ngreedy,random,4,3,0,1,0.750,0.000,0.250,6.50\n", CSV_HEADER)
+        );
+    }
+}
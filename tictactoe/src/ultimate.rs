@@ -0,0 +1,179 @@
+//! Ultimate tic-tac-toe: a 3x3 grid of ordinary 3x3 games. The cell a player
+//! picks inside a small board sends the opponent to the small board in the
+//! same position of the big grid. Winning a small board claims that cell of
+//! the big board, and three claimed cells in a row win the game.
+
+use crate::{CellState, Game, GameState, Player};
+
+pub const ULTIMATE_SIZE: usize = 3;
+
+#[derive(Clone)]
+pub struct UltimateGame {
+    pub boards: Vec<Vec<Game>>,
+    /// The big board. Its cells are claimed by whoever wins the matching
+    /// small board, so its `game_state` is the outcome of the whole game.
+    pub meta: Game,
+    pub first_player: Player,
+    pub current_player: Player,
+    /// The small board the current player has to play in, or `None` when
+    /// any unfinished board is allowed.
+    pub next_board: Option<(usize, usize)>,
+}
+
+impl UltimateGame {
+    pub fn new() -> Self {
+        Self {
+            boards: vec![vec![Game::new(ULTIMATE_SIZE, ULTIMATE_SIZE); ULTIMATE_SIZE]; ULTIMATE_SIZE],
+            meta: Game::new(ULTIMATE_SIZE, ULTIMATE_SIZE),
+            first_player: Player::X,
+            current_player: Player::X,
+            next_board: None,
+        }
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.meta.game_state
+    }
+
+    pub fn is_legal_board(&self, board_row: usize, board_col: usize) -> bool {
+        matches!(self.meta.game_state, GameState::Playing)
+            && matches!(self.boards[board_row][board_col].game_state, GameState::Playing)
+            && self.next_board.is_none_or(|next| next == (board_row, board_col))
+    }
+
+    pub fn make_move(&mut self, board_row: usize, board_col: usize, row: usize, col: usize) -> bool {
+        if !self.is_legal_board(board_row, board_col) {
+            return false;
+        }
+
+        let board = &mut self.boards[board_row][board_col];
+        board.current_player = self.current_player;
+        if !board.make_move(row, col) {
+            return false;
+        }
+
+        match board.game_state {
+            GameState::Win(player, _) => {
+                self.meta.board[board_row][board_col] = CellState::Occupied(player);
+                self.meta.check_game_state(board_row, board_col);
+            }
+            // Small boards are never timed.
+            GameState::Draw | GameState::Playing | GameState::Timeout(_) => {}
+        }
+
+        // Drawn small boards leave their big-board cell empty, so the big
+        // board's own draw check never fires for them.
+        let all_finished = self
+            .boards
+            .iter()
+            .flatten()
+            .all(|board| !matches!(board.game_state, GameState::Playing));
+        if all_finished && matches!(self.meta.game_state, GameState::Playing) {
+            self.meta.game_state = GameState::Draw;
+        }
+
+        self.next_board = match self.boards[row][col].game_state {
+            GameState::Playing => Some((row, col)),
+            _ => None,
+        };
+        self.current_player = self.current_player.other();
+        true
+    }
+
+    /// Starts the next round; players take turns at moving first.
+    pub fn reset(&mut self) {
+        let first_player = self.first_player.other();
+        *self = UltimateGame::new();
+        self.first_player = first_player;
+        self.current_player = first_player;
+    }
+}
+
+impl Default for UltimateGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `(row, col)` in `board` as `player`, whatever board the rules
+    /// would currently send them to.
+    fn force_move(game: &mut UltimateGame, board: (usize, usize), player: Player, row: usize, col: usize) {
+        game.next_board = Some(board);
+        game.current_player = player;
+        assert!(game.make_move(board.0, board.1, row, col));
+    }
+
+    #[test]
+    fn cell_played_picks_opponents_board() {
+        let mut game = UltimateGame::new();
+        assert!(game.make_move(1, 1, 0, 2));
+        assert_eq!(game.next_board, Some((0, 2)));
+        assert_eq!(game.current_player, Player::O);
+
+        assert!(!game.make_move(1, 1, 0, 0));
+        assert!(game.make_move(0, 2, 2, 2));
+        assert_eq!(game.next_board, Some((2, 2)));
+        assert_eq!(game.current_player, Player::X);
+    }
+
+    #[test]
+    fn winning_small_board_claims_big_board_cell() {
+        let mut game = UltimateGame::new();
+        for col in 0..ULTIMATE_SIZE {
+            force_move(&mut game, (1, 1), Player::O, 0, col);
+        }
+        assert!(matches!(game.boards[1][1].game_state, GameState::Win(Player::O, _)));
+        assert!(game.meta.board[1][1] == CellState::Occupied(Player::O));
+        assert!(matches!(game.game_state(), GameState::Playing));
+    }
+
+    #[test]
+    fn finished_board_frees_next_player() {
+        let mut game = UltimateGame::new();
+        for col in 0..ULTIMATE_SIZE {
+            force_move(&mut game, (1, 1), Player::X, 0, col);
+        }
+        assert_eq!(game.next_board, Some((0, 2)));
+
+        assert!(game.make_move(0, 2, 1, 1));
+        assert_eq!(game.next_board, None);
+        assert!(game.is_legal_board(0, 0));
+        assert!(!game.is_legal_board(1, 1));
+    }
+
+    #[test]
+    fn three_small_boards_in_a_row_win_the_game() {
+        let mut game = UltimateGame::new();
+        for board_col in 0..ULTIMATE_SIZE {
+            for col in 0..ULTIMATE_SIZE {
+                force_move(&mut game, (0, board_col), Player::X, 0, col);
+            }
+        }
+        assert!(matches!(game.game_state(), GameState::Win(Player::X, _)));
+        assert!(!game.is_legal_board(2, 2));
+        assert!(!game.make_move(2, 2, 0, 0));
+    }
+
+    #[test]
+    fn all_boards_finished_without_line_is_a_draw() {
+        let mut game = UltimateGame::new();
+        for board_row in 0..ULTIMATE_SIZE {
+            for board_col in 0..ULTIMATE_SIZE {
+                game.boards[board_row][board_col].game_state = GameState::Draw;
+            }
+        }
+        game.boards[2][2] = Game::new(ULTIMATE_SIZE, ULTIMATE_SIZE);
+
+        let moves = [(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0), (2, 2)];
+        for (i, &(row, col)) in moves.iter().enumerate() {
+            let player = if i % 2 == 0 { Player::X } else { Player::O };
+            force_move(&mut game, (2, 2), player, row, col);
+        }
+        assert!(matches!(game.boards[2][2].game_state, GameState::Draw));
+        assert!(matches!(game.game_state(), GameState::Draw));
+    }
+}
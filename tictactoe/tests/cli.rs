@@ -0,0 +1,46 @@
+//! Runs the real binary in terminal mode with moves piped into stdin.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_cli(args: &[&str], moves: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tictactoe"))
+        .arg("--cli")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start tictactoe");
+    child.stdin.take().unwrap().write_all(moves.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn piped_game_ends_with_result() {
+    let output = run_cli(&[], "b2\n1 1\nc1\na2\n3 1\n");
+    assert!(output.ends_with("  3 X . .\nX wins!\n"), "{}", output);
+}
+
+#[test]
+fn piped_game_uses_board_and_rules_flags() {
+    let output = run_cli(&["--size", "6", "--rules", "notakto"], "a1\nb1\nc1\nquit\n");
+    assert!(output.contains("    a b c d e f\n"), "{}", output);
+    assert!(output.contains("  2 # # # . . .\n"), "{}", output);
+    assert!(output.ends_with("Game abandoned.\n"), "{}", output);
+}
+
+#[test]
+fn tournament_prints_csv() {
+    let output = Command::new(env!("CARGO_BIN_EXE_tictactoe"))
+        .args(["--tournament", "--games", "4", "--strategies", "first-empty,random"])
+        .output()
+        .expect("failed to start tictactoe");
+    assert!(output.status.success());
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", csv);
+    assert!(lines[0].starts_with("x,o,games,"), "{}", csv);
+    assert!(lines[1].starts_with("first-empty,first-empty,4,4,0,0,"), "{}", csv);
+}